@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::common::Pattern;
+use crate::dictionary::WordId;
+
+pub const ANSWER_SET_WORDS: usize = 37;
+
+// Fixed size bitset over answer ids, 37 * 64 = 2368 bits covers the 2315 answers.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AnswerSet {
+    bits: [u64; ANSWER_SET_WORDS]
+}
+
+impl Default for AnswerSet {
+    fn default() -> Self {
+        AnswerSet::new()
+    }
+}
+
+impl fmt::Debug for AnswerSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl FromIterator<WordId> for AnswerSet {
+    fn from_iter<I: IntoIterator<Item = WordId>>(iter: I) -> Self {
+        let mut set = AnswerSet::new();
+        for id in iter {
+            set.insert(id);
+        }
+        set
+    }
+}
+
+impl AnswerSet {
+    pub const CAPACITY: usize = ANSWER_SET_WORDS * 64;
+
+    pub fn new() -> Self {
+        AnswerSet {
+            bits: [0; ANSWER_SET_WORDS]
+        }
+    }
+
+    // The answers 0..count.
+    pub fn full(count: usize) -> Self {
+        assert!(count <= AnswerSet::CAPACITY, "Too many answers for AnswerSet.");
+
+        let mut set = AnswerSet::new();
+        for word in set.bits.iter_mut().take(count / 64) {
+            *word = u64::MAX;
+        }
+        if !count.is_multiple_of(64) {
+            set.bits[count / 64] = (1 << (count % 64)) - 1;
+        }
+        set
+    }
+
+    pub fn insert(&mut self, id: WordId) {
+        self.bits[id as usize / 64] |= 1 << (id % 64);
+    }
+
+    pub fn remove(&mut self, id: WordId) {
+        self.bits[id as usize / 64] &= !(1 << (id % 64));
+    }
+
+    pub fn contains(&self, id: WordId) -> bool {
+        self.bits[id as usize / 64] & (1 << (id % 64)) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|word| *word == 0)
+    }
+
+    pub fn first(&self) -> Option<WordId> {
+        self.iter().next()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = WordId> + '_ {
+        self.bits.iter().enumerate().flat_map(|(i, word)| {
+            let mut word = *word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some((i * 64 + bit) as WordId)
+            })
+        })
+    }
+
+    // Split by the pattern each answer gives, row is a PatternTable row of the guess.
+    pub fn partition(&self, row: &[Pattern]) -> BTreeMap<Pattern, AnswerSet> {
+        let mut groups = BTreeMap::new();
+        for id in self.iter() {
+            groups.entry(row[id as usize]).or_insert_with(AnswerSet::new).insert(id);
+        }
+        groups
+    }
+
+    // The answers giving exactly this pattern.
+    pub fn filter(&self, row: &[Pattern], pattern: Pattern) -> AnswerSet {
+        self.iter().filter(|id| row[*id as usize] == pattern).collect()
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::common::{Best, Task};
+use crate::config::SearchConfig;
+use crate::dictionary::{Dictionary, WordId};
+use crate::answer_set::AnswerSet;
+use crate::transposition::{StateKey, TranspositionTable, read_best, write_best};
+use crate::encoding::{StableHasher, read_u8, read_u16, read_u64};
+
+const MAGIC: &[u8; 4] = b"WSCP";
+const VERSION: u8 = 1;
+
+// What a checkpoint files its results under.
+pub trait RecordKey: Ord + Sized {
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+    fn read<R: Read>(reader: &mut R) -> io::Result<Self>;
+}
+
+// A parallel_wrapper task: pattern, second guess and second pattern.
+impl RecordKey for Task {
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let (pattern, second_guess, second_pattern) = self;
+        writer.write_all(&pattern.to_le_bytes())?;
+        writer.write_all(&second_guess.to_le_bytes())?;
+        writer.write_all(&second_pattern.to_le_bytes())
+    }
+
+    fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
+        Ok((read_u16(reader)?, read_u16(reader)?, read_u16(reader)?))
+    }
+}
+
+// A leaderboard opener: hard mode and the word.
+impl RecordKey for (bool, WordId) {
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let (hard_mode, word) = self;
+        writer.write_all(&[*hard_mode as u8])?;
+        writer.write_all(&word.to_le_bytes())
+    }
+
+    fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
+        Ok((read_u8(reader)? != 0, read_u16(reader)?))
+    }
+}
+
+// Finished results, appended as they complete so a run that was stopped resumes
+// where it left off. Keyed by parallel_wrapper task unless told otherwise.
+//
+// Layout: magic, version, fingerprint, then per result its key, a byte telling
+// whether it found a tree and the tree's Best in the solver cache encoding. Only
+// whole records count, a record torn by a crash is dropped when the file is
+// opened again.
+#[derive(Debug)]
+pub struct Checkpoint<K = Task> {
+    file: Mutex<File>,
+    key: PhantomData<K>
+}
+
+fn read_record<K: RecordKey, R: Read>(reader: &mut R) -> io::Result<(K, Best)> {
+    let key = K::read(reader)?;
+    let best = match read_u8(reader)? {
+        0 => Best::new(),
+        _ => read_best(reader)?
+    };
+    Ok((key, best))
+}
+
+impl Checkpoint {
+    // Everything a task result depends on: the solver cache fingerprint, the beam and
+    // its heuristic, the small set shortcut and which words the run starts from.
+    pub fn fingerprint(dictionary: &Dictionary, config: &SearchConfig, start_word: WordId, answers: &AnswerSet, availables: &BTreeSet<WordId>) -> u64 {
+        let key = StateKey::new(answers, availables);
+        let mut hasher = StableHasher::new(0);
+        hasher.add(TranspositionTable::fingerprint(dictionary, config));
+        hasher.add(config.shape());
+        hasher.add(config.small_set_threshold as u64);
+        hasher.add(start_word as u64);
+        hasher.add(key.hash);
+        hasher.add(key.check);
+        hasher.0
+    }
+}
+
+impl<K: RecordKey> Checkpoint<K> {
+    // The whole records of a file written for `fingerprint`, with the bytes they
+    // end at. An empty file holds none, a record cut short ends the records.
+    fn read_records<P: AsRef<Path>>(path: P, fingerprint: u64) -> io::Result<(BTreeMap<K, Best>, u64)> {
+        let file = File::open(path)?;
+        if file.metadata()?.len() == 0 {
+            return Ok((BTreeMap::new(), 0));
+        }
+
+        let mut reader = BufReader::new(file);
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a checkpoint file"));
+        }
+        if read_u8(&mut reader)? != VERSION {
+            return Err(invalid("unsupported checkpoint version"));
+        }
+        if read_u64(&mut reader)? != fingerprint {
+            return Err(invalid("checkpoint was written for different word lists or settings"));
+        }
+
+        let mut done = BTreeMap::new();
+        let mut valid_len = reader.stream_position()?;
+        loop {
+            match read_record(&mut reader) {
+                Ok((key, best)) => {
+                    done.insert(key, best);
+                    valid_len = reader.stream_position()?;
+                },
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err)
+            }
+        }
+        Ok((done, valid_len))
+    }
+
+    // The results in a file written for `fingerprint`, without opening it for more.
+    pub fn read<P: AsRef<Path>>(path: P, fingerprint: u64) -> io::Result<BTreeMap<K, Best>> {
+        Checkpoint::read_records(path, fingerprint).map(|(done, _)| done)
+    }
+
+    // Opens `path` for appending and returns the results it already holds, a
+    // missing file starts empty. Only a record torn by a crash is cut off: a file
+    // written for another fingerprint, or one that cannot be read, is an error
+    // and stays as it is.
+    pub fn open<P: AsRef<Path>>(path: P, fingerprint: u64) -> io::Result<(Self, BTreeMap<K, Best>)> {
+        let (done, valid_len) = match Checkpoint::read_records(&path, fingerprint) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (BTreeMap::new(), 0),
+            records => records?
+        };
+
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        file.set_len(valid_len)?;
+        file.seek(SeekFrom::End(0))?;
+        if valid_len == 0 {
+            file.write_all(MAGIC)?;
+            file.write_all(&[VERSION])?;
+            file.write_all(&fingerprint.to_le_bytes())?;
+        }
+
+        Ok((Checkpoint { file: Mutex::new(file), key: PhantomData }, done))
+    }
+
+    // Appends one finished result, safe to call from several threads. Each record is
+    // handed to the OS whole, so a killed process loses at most the results in flight.
+    pub fn record(&self, key: &K, best: &Best) -> io::Result<()> {
+        let mut record = Vec::new();
+        key.write(&mut record)?;
+        record.push(best.has_result as u8);
+        if best.has_result {
+            write_best(&mut record, best)?;
+        }
+
+        self.file.lock().unwrap().write_all(&record)
+    }
+}
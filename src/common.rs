@@ -1,9 +1,49 @@
-use std::cmp::max;
-use std::collections::{BTreeMap, BTreeSet};
+use std::borrow::Cow;
+use std::cmp::{max, Ordering};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, AtomicU64};
 use serde::{Deserialize, Serialize};
-use serde_json::Result;
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord)]
+use crate::dictionary::{Dictionary, WordId};
+use crate::game::Checker;
+use crate::objective::Objective;
+use crate::tree_file::{is_tree_file, read_tree_file, write_tree_file};
+
+// Feedback for one guess, packed in base 3 with the first letter as the lowest digit.
+// u16 holds every pattern up to MAX_WORD_LEN letters (3^8 = 6561).
+pub type Pattern = u16;
+
+pub const MIN_WORD_LEN: usize = 3;
+pub const MAX_WORD_LEN: usize = 8;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum WordListError {
+    Empty,
+    UnsupportedLength { word: String, length: usize },
+    MixedLengths { expected: usize, word: String },
+}
+
+impl fmt::Display for WordListError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WordListError::Empty => write!(f, "word list is empty"),
+            WordListError::UnsupportedLength { word, length } => write!(
+                f, "word {:?} has {} letters, only {} to {} are supported", word, length, MIN_WORD_LEN, MAX_WORD_LEN
+            ),
+            WordListError::MixedLengths { expected, word } => write!(
+                f, "word {:?} has {} letters, expected {}", word, word.chars().count(), expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WordListError {}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Hash, PartialOrd, Ord)]
 pub struct Restriction {
     pub required_green: BTreeMap<usize, char>,
     pub required_yellow: BTreeMap<char, usize>
@@ -17,7 +57,7 @@ impl Restriction {
         }
     }
 
-    pub fn from(guess: &str, pattern: u8) -> Self {
+    pub fn from(guess: &str, pattern: Pattern) -> Self {
         let mut required_green = BTreeMap::new();
         let mut required_yellow = BTreeMap::new();
 
@@ -73,7 +113,7 @@ impl Restriction {
         }
 
         for (c, required_count) in self.required_yellow.iter() {
-            if counter.get(&c).unwrap_or(&0) < &required_count {
+            if counter.get(c).unwrap_or(&0) < required_count {
                 return false;
             }
         }
@@ -83,107 +123,297 @@ impl Restriction {
 }
 
 
+// Depths tracked by Best::depth_counts, the last slot also holds anything deeper.
+pub const DEPTH_SLOTS: usize = 16;
+
 #[derive(Debug, Clone, PartialEq)]
-pub struct Best<'a> {
+pub struct Best {
     pub has_result: bool,
     pub max_level: u8,
     pub total_count: u32,
-    pub decision_tree: DecisionTree<'a>
+    // Answers found with each number of guesses, relative like max_level.
+    pub depth_counts: [u32; DEPTH_SLOTS],
+    // total_count with every answer scaled by its weight in SearchConfig::priors.
+    pub weighted_count: f64,
+    pub decision_tree: DecisionTree
 }
 
-impl<'a> Best<'a> {
+impl Default for Best {
+    fn default() -> Self {
+        Best::new()
+    }
+}
+
+impl Best {
     pub fn new() -> Self {
         Best {
             has_result: false,
             max_level: 0,
             total_count: u32::MAX,
+            depth_counts: [0; DEPTH_SLOTS],
+            weighted_count: f64::INFINITY,
             decision_tree: DecisionTree::new()
         }
     }
 
-    pub fn init(guess: &'a str, total_count: u32) -> Self {
+    // Nothing guessed and nothing found, the start of a sum.
+    pub fn empty() -> Self {
         Best {
             has_result: true,
             max_level: 0,
-            total_count: total_count,
-            decision_tree: DecisionTree::from(guess, BTreeMap::new())
+            total_count: 0,
+            depth_counts: [0; DEPTH_SLOTS],
+            weighted_count: 0.0,
+            decision_tree: DecisionTree::new()
         }
     }
 
-    pub fn better(&mut self, other: Best<'a>) {
+    // The branch after the success pattern, nothing left to guess.
+    pub fn success() -> Self {
+        let mut best = Best::empty();
+        best.depth_counts[0] = 1;
+        best
+    }
+
+    // A single answer left, guess it.
+    pub fn single(answer: WordId, word_len: usize) -> Self {
+        let mut best = Best::empty();
+        best.max_level = 1;
+        best.total_count = 1;
+        best.depth_counts[1] = 1;
+        best.weighted_count = 1.0;
+        best.decision_tree = DecisionTree::from(answer, BTreeMap::from([(Checker::success_pattern(word_len), DecisionTree::new())]));
+        best
+    }
+
+    pub fn init(guess: WordId, total_count: u32) -> Self {
+        let mut best = Best::empty();
+        best.total_count = total_count;
+        best.weighted_count = total_count as f64;
+        best.decision_tree = DecisionTree::from(guess, BTreeMap::new());
+        best
+    }
+
+    // Statistics only: no finished tree for `answers` answers can do better than
+    // one found by its first guess and the rest by the second, `max_level` deep.
+    // No answers need no guesses.
+    pub fn bound(max_level: u8, answers: u32, weighted_count: f64) -> Self {
+        if answers == 0 {
+            return Best::empty();
+        }
+
+        let mut best = Best::empty();
+        best.max_level = max_level;
+        best.total_count = 2 * answers - 1;
+        best.depth_counts[1] = 1;
+        best.depth_counts[2] = answers - 1;
+        best.weighted_count = weighted_count;
+        best
+    }
+
+    // Replaces the weighted_count set from the answer count.
+    pub fn with_weight(mut self, weighted_count: f64) -> Self {
+        self.weighted_count = weighted_count;
+        self
+    }
+
+    // Adds the guess that leads to this result, every answer moves one deeper.
+    pub fn finish(&mut self) {
+        self.max_level += 1;
+        let deepest = self.depth_counts[DEPTH_SLOTS - 1];
+        self.depth_counts.copy_within(0..DEPTH_SLOTS - 1, 1);
+        self.depth_counts[0] = 0;
+        self.depth_counts[DEPTH_SLOTS - 1] += deepest;
+    }
+
+    // Sums the statistics of a sibling branch, the decision tree is left alone.
+    pub fn merge(&mut self, other: &Best) {
+        self.max_level = max(self.max_level, other.max_level);
+        self.total_count += other.total_count;
+        for (count, other_count) in self.depth_counts.iter_mut().zip(other.depth_counts.iter()) {
+            *count += other_count;
+        }
+        self.weighted_count += other.weighted_count;
+    }
+
+    // Keeps `other` after its guess when the objective prefers it.
+    pub fn better(&mut self, mut other: Best, objective: &dyn Objective) {
         if !other.has_result {
             return;
         }
 
-        if self.total_count > other.total_count || (self.total_count == other.total_count && self.max_level > other.max_level + 1) {
-            self.max_level = other.max_level + 1;
-            self.total_count = other.total_count;
-            self.decision_tree = other.decision_tree;
-            self.has_result = true;
+        other.finish();
+
+        if !self.has_result || objective.compare(&other, self) == Ordering::Less {
+            *self = other;
         }
     }
 
-    pub fn update(&mut self, pattern: u8, other: Best<'a>) {
+    pub fn update(&mut self, pattern: Pattern, other: Best) {
         if !other.has_result {
             return;
         }
 
-        self.max_level = max(self.max_level, other.max_level);
-        self.total_count += other.total_count;
+        self.merge(&other);
         self.decision_tree.add_branch(pattern, other.decision_tree);
         self.has_result = true;
     }
-}   
+}
+
+pub type Task = (Pattern, WordId, Pattern);
 
-pub type Cache<'a> = BTreeMap<Restriction, BTreeMap<BTreeSet<&'a str>, BTreeMap<u8, Best<'a>>>>;
-pub type Task<'a> = (u8, &'a str, u8);
+// Leaves (the branch after a success pattern) have no guess.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DecisionTree {
+    pub guess: Option<WordId>,
+    pub branch: BTreeMap<Pattern, DecisionTree>
+}
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct DecisionTree<'a> {
-    pub guess: &'a str,
-    pub branch: BTreeMap<u8, DecisionTree<'a>>
+// The string form written to JSON, leaves keep the historical empty guess.
+#[derive(Serialize, Deserialize)]
+struct JsonTree<'a> {
+    #[serde(borrow)]
+    guess: Cow<'a, str>,
+    #[serde(borrow)]
+    branch: BTreeMap<Pattern, JsonTree<'a>>
 }
 
-impl<'a> DecisionTree<'a> {
+// "salet:00120 crane:01000", the guesses and patterns leading to a node.
+fn describe_path(dictionary: &Dictionary, path: &[(WordId, Pattern)]) -> String {
+    if path.is_empty() {
+        return "the root".to_string();
+    }
+    let steps: Vec<_> = path.iter().map(|(guess, pattern)| {
+        format!("{}:{}", dictionary.word(*guess), Checker::format_pattern(*pattern, dictionary.word_len()))
+    }).collect();
+    steps.join(" ")
+}
+
+impl DecisionTree {
     pub fn new() -> Self {
         DecisionTree {
-            guess: "",
+            guess: None,
             branch: BTreeMap::new()
         }
     }
 
-    pub fn from(guess:&'a str, branch:BTreeMap<u8, DecisionTree<'a>>) -> Self {
+    pub fn from(guess: WordId, branch:BTreeMap<Pattern, DecisionTree>) -> Self {
         DecisionTree {
-            guess,
+            guess: Some(guess),
             branch
         }
     }
 
-    pub fn guess(&self) -> String {
-        self.guess.to_string()
+    pub fn guess(&self) -> Option<WordId> {
+        self.guess
     }
 
-    pub fn add_branch(&mut self, pattern: u8, tree: DecisionTree<'a>) {
+    pub fn add_branch(&mut self, pattern: Pattern, tree: DecisionTree) {
         self.branch.insert(pattern, tree);
     }
 
+    pub fn node_count(&self) -> usize {
+        1 + self.branch.values().map(|tree| tree.node_count()).sum::<usize>()
+    }
+
     pub fn next (
-        current: &'a DecisionTree,
-        pattern: u8
-    ) -> &'a DecisionTree<'a> {
+        current: &DecisionTree,
+        pattern: Pattern
+    ) -> &DecisionTree {
         current.branch.get(&pattern).unwrap()
     }
 
-    pub fn to_json(&self) -> Result<()> {
-        println!("{}", serde_json::to_string(self)?);
-        Ok(())
+    fn to_json_tree<'a>(&self, dictionary: &'a Dictionary) -> JsonTree<'a> {
+        JsonTree {
+            guess: Cow::Borrowed(self.guess.map_or("", |guess| dictionary.word(guess))),
+            branch: self.branch.iter().map(|(pattern, tree)| (*pattern, tree.to_json_tree(dictionary))).collect()
+        }
+    }
+
+    // `path` holds the guesses and patterns that led to `json`, for the errors.
+    fn from_json_tree(json: &JsonTree, dictionary: &Dictionary, path: &mut Vec<(WordId, Pattern)>) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        if json.guess.is_empty() {
+            if !json.branch.is_empty() {
+                return Err(invalid(format!("tree has branches but no guess at {}", describe_path(dictionary, path))));
+            }
+            return Ok(DecisionTree::new());
+        }
+
+        let guess = dictionary.id(&json.guess).map_err(|err| invalid(format!("tree has an {} at {}", err, describe_path(dictionary, path))))?;
+        let mut tree = DecisionTree::from(guess, BTreeMap::new());
+
+        for (pattern, branch) in json.branch.iter() {
+            if *pattern as usize >= Checker::pattern_count(dictionary.word_len()) {
+                return Err(invalid(format!("tree has pattern {} after {} at {}", pattern, dictionary.word(guess), describe_path(dictionary, path))));
+            }
+
+            path.push((guess, *pattern));
+            let branch = DecisionTree::from_json_tree(branch, dictionary, path)?;
+            path.pop();
+            tree.add_branch(*pattern, branch);
+        }
+
+        Ok(tree)
+    }
+
+    pub fn to_json(&self, dictionary: &Dictionary) -> String {
+        serde_json::to_string(&self.to_json_tree(dictionary)).expect("string keys and values always serialize")
+    }
+
+    // Reads the JSON to_json writes. Fails with InvalidData naming the word or
+    // pattern and where it is when the tree does not fit the dictionary.
+    pub fn from_json(content: &str, dictionary: &Dictionary) -> io::Result<Self> {
+        let json: JsonTree = serde_json::from_str(content)?;
+        DecisionTree::from_json_tree(&json, dictionary, &mut Vec::new())
+    }
+
+    pub fn save_to_path<P: AsRef<Path>>(&self, path: P, dictionary: &Dictionary) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, &self.to_json_tree(dictionary))?;
+        writer.flush()
+    }
+
+    // The compact encoding of tree_file, with a header naming the word lists.
+    pub fn to_binary(&self, dictionary: &Dictionary) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_tree_file(&mut bytes, self, dictionary).expect("writing to a Vec cannot fail");
+        bytes
+    }
+
+    pub fn from_binary(mut bytes: &[u8], dictionary: &Dictionary) -> io::Result<Self> {
+        read_tree_file(&mut bytes, dictionary)
+    }
+
+    pub fn save_binary_to_path<P: AsRef<Path>>(&self, path: P, dictionary: &Dictionary) -> io::Result<()> {
+        fs::write(path, self.to_binary(dictionary))
+    }
+
+    // A tree saved by save_to_path, save_binary_to_path or to_json, data/wordle.json
+    // among them. The format is told from the file's first bytes.
+    pub fn load_from_path<P: AsRef<Path>>(path: P, dictionary: &Dictionary) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        if is_tree_file(&bytes) {
+            return DecisionTree::from_binary(&bytes, dictionary);
+        }
+        let content = String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        DecisionTree::from_json(&content, dictionary)
     }
 }
 
 
-#[derive(Debug)]
+// Atomic so the threads of one search can share it.
+#[derive(Debug, Default)]
 pub struct Counter{
-    pub result_counter: u32,
-    pub no_result_counter: u32,
-    pub baseline_counter: u32,
-}
\ No newline at end of file
+    pub result_counter: AtomicU32,
+    pub no_result_counter: AtomicU32,
+    pub baseline_counter: AtomicU32,
+    // Calls to Searcher::search.
+    pub node_counter: AtomicU64,
+    // See SearchStats.
+    pub beam_cut_counter: AtomicU64,
+    pub bound_prune_counter: AtomicU64,
+    pub cache_hit_counters: [AtomicU64; DEPTH_SLOTS],
+    pub cache_miss_counters: [AtomicU64; DEPTH_SLOTS]
+}
@@ -0,0 +1,205 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::objective::{Objective, TotalGuesses, parse_objective};
+use crate::heuristic::{GuessHeuristic, CurrentScore, parse_heuristic};
+use crate::prior::Priors;
+use crate::encoding::StableHasher;
+use crate::observer::{Observer, Silent};
+
+// How many of the ranked guesses the search tries at each depth.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Beam {
+    Fixed(usize),
+    // Width per depth, depths past the end reuse the last width.
+    PerDepth(Vec<usize>),
+    Unlimited
+}
+
+impl Beam {
+    pub fn width(&self, depth: u8) -> usize {
+        match self {
+            Beam::Fixed(width) => *width,
+            Beam::PerDepth(widths) => widths
+                .get(depth as usize)
+                .or_else(|| widths.last())
+                .cloned()
+                .unwrap_or(usize::MAX),
+            Beam::Unlimited => usize::MAX
+        }
+    }
+}
+
+// "13", "15,13,8" or "unlimited".
+impl FromStr for Beam {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "unlimited" {
+            return Ok(Beam::Unlimited);
+        }
+
+        let widths = s
+            .split(',')
+            .map(|width| width.trim().parse::<usize>().map_err(|_| format!("invalid beam width {:?}", width)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match widths.as_slice() {
+            [width] => Ok(Beam::Fixed(*width)),
+            _ => Ok(Beam::PerDepth(widths))
+        }
+    }
+}
+
+// Limits for an anytime search. Once one is reached the search stops trying
+// more guesses and completes each open subtree with the first tree it finds.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub time: Option<Duration>,
+    // Calls to Searcher::search, shared by every task of a parallel run.
+    pub nodes: Option<u64>
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchConfig {
+    // Guesses numbered from 0 up to max_turns - 1 are searched, a set already
+    // down to one answer is still guessed on the turn after.
+    pub max_turns: u8,
+    pub beam: Beam,
+    // Orders the guesses the beam picks from.
+    pub heuristic: Arc<dyn GuessHeuristic>,
+    pub use_cache: bool,
+    // Memory cap of the transposition table in bytes, unbounded when None.
+    pub cache_bytes: Option<usize>,
+    // Solver cache file the wrappers start from and write back, see TranspositionTable::save_to_path.
+    pub cache_path: Option<PathBuf>,
+    // File parallel_wrapper records finished tasks in and resumes from, see Checkpoint.
+    pub checkpoint_path: Option<PathBuf>,
+    // Hard mode only keeps guesses that satisfy the revealed greens and yellows,
+    // normal mode keeps the whole guess list at every depth.
+    pub hard_mode: bool,
+    // Sets of this size or smaller only try their own answers as guesses.
+    pub small_set_threshold: usize,
+    // What Best::better keeps and the search prunes against.
+    pub objective: Arc<dyn Objective>,
+    // How likely each answer is. Weighs weighted_count and the heuristics'
+    // buckets, every answer counts the same when None.
+    pub priors: Option<Arc<Priors>>,
+    // Try every guess with no beam or small set shortcut, so a result is proven
+    // optimal for its answers and guesses. Slow beyond a few hundred answers.
+    pub exact: bool,
+    pub budget: Budget,
+    // Sets of at least this many answers try their guesses in parallel, every
+    // subtree sharing the cache and the best result of its siblings so far.
+    // usize::MAX keeps the search on one thread.
+    pub parallel_threshold: usize,
+    // Told about the progress of the wrappers, can cancel the search.
+    pub observer: Arc<dyn Observer>
+}
+
+// Trait objects have no PartialEq, objectives and heuristics compare by name.
+// The observer is left out, it does not change what a search finds.
+impl PartialEq for SearchConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.max_turns == other.max_turns
+            && self.beam == other.beam
+            && self.heuristic.name() == other.heuristic.name()
+            && self.use_cache == other.use_cache
+            && self.cache_bytes == other.cache_bytes
+            && self.cache_path == other.cache_path
+            && self.checkpoint_path == other.checkpoint_path
+            && self.hard_mode == other.hard_mode
+            && self.small_set_threshold == other.small_set_threshold
+            && self.objective.name() == other.objective.name()
+            && self.priors == other.priors
+            && self.exact == other.exact
+            && self.budget == other.budget
+            && self.parallel_threshold == other.parallel_threshold
+    }
+}
+
+impl Eq for SearchConfig {}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            max_turns: 6,
+            // limit 15 can get best results.
+            beam: Beam::Fixed(13),
+            heuristic: Arc::new(CurrentScore),
+            use_cache: false,
+            cache_bytes: None,
+            cache_path: None,
+            checkpoint_path: None,
+            hard_mode: true,
+            small_set_threshold: 3,
+            objective: Arc::new(TotalGuesses),
+            priors: None,
+            exact: false,
+            budget: Budget::default(),
+            parallel_threshold: usize::MAX,
+            observer: Arc::new(Silent)
+        }
+    }
+}
+
+impl SearchConfig {
+    // What decides which guesses a beam keeps: its widths and the heuristic
+    // ranking them. Results the beam cut guesses from are only final for searches
+    // of the same shape.
+    pub fn shape(&self) -> u64 {
+        let mut hasher = StableHasher::new(0);
+        match &self.beam {
+            Beam::Fixed(width) => {
+                hasher.add(0);
+                hasher.add(*width as u64);
+            },
+            Beam::PerDepth(widths) => {
+                hasher.add(1);
+                hasher.add(widths.len() as u64);
+                widths.iter().for_each(|width| hasher.add(*width as u64));
+            },
+            Beam::Unlimited => hasher.add(2)
+        }
+        hasher.add_bytes(self.heuristic.name().as_bytes());
+        hasher.0
+    }
+
+    // Overrides the defaults from command line flags, returns the arguments it did not use.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<(Self, Vec<String>), String> {
+        let mut config = SearchConfig::default();
+        let mut rest = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or_else(|| format!("missing value for {}", name));
+
+            match arg.as_str() {
+                "--max-turns" => config.max_turns = value("--max-turns")?.parse().map_err(|_| "invalid --max-turns".to_string())?,
+                "--beam" => config.beam = value("--beam")?.parse()?,
+                "--heuristic" => config.heuristic = parse_heuristic(&value("--heuristic")?)?,
+                "--small-set" => config.small_set_threshold = value("--small-set")?.parse().map_err(|_| "invalid --small-set".to_string())?,
+                "--objective" => config.objective = Arc::from(parse_objective(&value("--objective")?)?),
+                "--exact" => config.exact = true,
+                "--time-limit" => config.budget.time = Some(value("--time-limit")?.parse().ok().and_then(|secs| Duration::try_from_secs_f64(secs).ok()).ok_or_else(|| "invalid --time-limit".to_string())?),
+                "--node-limit" => config.budget.nodes = Some(value("--node-limit")?.parse().map_err(|_| "invalid --node-limit".to_string())?),
+                "--parallel" => config.parallel_threshold = value("--parallel")?.parse().map_err(|_| "invalid --parallel".to_string())?,
+                "--cache" => config.use_cache = true,
+                "--no-cache" => config.use_cache = false,
+                "--cache-file" => {
+                    config.cache_path = Some(PathBuf::from(value("--cache-file")?));
+                    config.use_cache = true;
+                },
+                "--checkpoint" => config.checkpoint_path = Some(PathBuf::from(value("--checkpoint")?)),
+                "--cache-mb" => config.cache_bytes = Some(value("--cache-mb")?.parse::<usize>().map_err(|_| "invalid --cache-mb".to_string())? << 20),
+                "--hard" => config.hard_mode = true,
+                "--normal" => config.hard_mode = false,
+                _ => rest.push(arg)
+            }
+        }
+
+        Ok((config, rest))
+    }
+}
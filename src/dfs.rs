@@ -1,268 +1,492 @@
-use std::sync::{Arc, Mutex};
+use std::cmp::Ordering;
 use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering as AtomicOrdering};
+use std::time::Instant;
+
+use rayon::prelude::*;
 
 use crate::utils::*;
-use crate::common::{Restriction, Best, Cache, DecisionTree, Counter};
+use crate::common::{Restriction, Best, Counter, Pattern, DEPTH_SLOTS};
+use crate::config::{SearchConfig, Budget};
 use crate::game::Checker;
+use crate::table::PatternTable;
+use crate::dictionary::WordId;
+use crate::answer_set::AnswerSet;
+use crate::objective::Objective;
+use crate::transposition::{Lookup, ShardedTable, StateKey, TranspositionTable};
+use crate::observer::{Observer, SearchStats};
+
+// How close a result of Searcher::search from the root is to the best possible.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Certificate {
+    pub proven_optimal: bool,
+    // Statistics only, no tree for the same answers and guesses compares below it.
+    pub lower_bound: Best,
+    // Objective::score of the result minus that of the lower bound.
+    pub gap: f64
+}
 
-const MAX_TURNS: u8 = 5;
+// Spends a Budget, clones share the node count so parallel tasks spend it together.
+// An observer that cancels ends it early.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    budget: Budget,
+    started: Instant,
+    nodes: Arc<AtomicU64>,
+    observer: Arc<dyn Observer>
+}
 
+impl Clock {
+    pub fn start(budget: &Budget, observer: Arc<dyn Observer>) -> Self {
+        Clock {
+            budget: budget.clone(),
+            started: Instant::now(),
+            nodes: Arc::new(AtomicU64::new(0)),
+            observer
+        }
+    }
 
-pub fn dfs_with_cache<'a>(current: u8, answers: &BTreeSet<&'a str>, availables: &BTreeSet<&'a str>, restrictions: Restriction, cache:&Arc<Mutex<Cache<'a>>>, use_limit: bool, counter:&mut Counter) -> Best<'a> {
+    // Nodes searched so far by every clone.
+    pub fn nodes(&self) -> u64 {
+        self.nodes.load(AtomicOrdering::Relaxed)
+    }
 
-    if current > MAX_TURNS {
-        return Best::new();
+    pub fn tick(&self) {
+        self.nodes.fetch_add(1, AtomicOrdering::Relaxed);
     }
 
-    let mut best_of_all_guess = Best::new();
+    pub fn exhausted(&self) -> bool {
+        self.budget.nodes.is_some_and(|nodes| self.nodes() >= nodes)
+            || self.budget.time.is_some_and(|time| self.started.elapsed() >= time)
+            || self.observer.cancelled()
+    }
+}
 
-    if let Some(restrictions_cache) = cache.lock().unwrap().get(&restrictions) {
-        if let Some(answers_cache) = restrictions_cache.get(answers) {
+// Best finished guess among the siblings of one search node, shared by the
+// threads trying them. Ties go to the better ranked guess so a parallel search
+// returns the same tree as a sequential one.
+struct SharedBest {
+    best: Mutex<(Best, usize)>,
+    // Objective::score of `best`, lets most pruning checks skip the lock.
+    score: AtomicU64,
+    // A cached result with fewer turns. It prunes guesses that cannot reach it
+    // but is only returned when none does: which cached tree other threads or an
+    // earlier run left behind must not choose between trees that tie.
+    baseline: Best,
+    baseline_score: f64,
+    // A beam cut guesses below one of the siblings, the best may not be final.
+    cut: AtomicBool
+}
 
-            // Cached Result:
-            if let Some(level_cache) = answers_cache.get(&current) {
-                counter.result_counter += 1;
-                return level_cache.clone();
-            }
+impl SharedBest {
+    fn new(baseline: Best, objective: &dyn Objective) -> Self {
+        let baseline_score = if baseline.has_result { objective.score(&baseline) } else { f64::INFINITY };
+        SharedBest {
+            best: Mutex::new((Best::new(), 0)),
+            score: AtomicU64::new(f64::INFINITY.to_bits()),
+            baseline,
+            baseline_score,
+            cut: AtomicBool::new(false)
+        }
+    }
 
-            // Cached No Result:
-            // for level in 0..(current - 1) {
-            //     if let Some(level_cache) = answers_cache.get(&level) {
-            //         if !level_cache.has_result || level_cache.max_level + current <= MAX_TURNS {
-            //             counter.no_result_counter += 1;
-            //             return level_cache.clone()
-            //         }
-            //     }
-            // }
-            
-            // Cached Base Line:
-            for level in (current + 1) .. MAX_TURNS {
-                if let Some(level_cache) = answers_cache.get(&level) {
-                    counter.baseline_counter += 1;
-                    best_of_all_guess = level_cache.clone();
-                    break
-                }
-            }
+    fn score(&self) -> f64 {
+        f64::from_bits(self.score.load(AtomicOrdering::Relaxed))
+    }
+
+    fn has_result(&self) -> bool {
+        self.score().min(self.baseline_score) < f64::INFINITY
+    }
+
+    // Whether a finished tree ranked at `index` could replace the best one.
+    // Trees scoring the same as the baseline still can.
+    fn admits(&self, estimate: &Best, index: usize, objective: &dyn Objective) -> bool {
+        let score = self.score().min(self.baseline_score);
+        if objective.score(estimate) > score + 1e-6 * score.abs().max(1.0) {
+            return false;
         }
+        Self::beats(estimate, index, &self.best.lock().unwrap(), objective)
     }
 
-    let mut group_patterns = BTreeSet::<BTreeMap<u8, BTreeSet<&str>>>::new();
-    let mut preprocess_by_guess:Vec<_> = availables
-        .iter()
-        .filter_map(|guess| {
-            let (guess, entropy, groups) = get_entropy_sum(guess, &answers);
-            if group_patterns.contains(&groups) {
-                return None
-            }
-    
-            group_patterns.insert(groups.clone());
-
-            return Some((guess, entropy, groups))
-        })
-        .collect();
-    
-    preprocess_by_guess
-        .sort_by_cached_key(|(_, entropy, _)| *entropy);
-    
-    let top_guesses:Vec<_> = if use_limit {
-        let length = preprocess_by_guess.len();
-        preprocess_by_guess
-            .into_iter()
-            .take(limit(length))
-            .collect()
-    } else {
-        preprocess_by_guess
-    };
-    
-    for (guess, entropy, groups) in top_guesses {
-
-        let mut lower_bound = entropy;
-        let mut current_guess = Best::init(guess, answers.len() as u32);
-
-        if best_of_all_guess.has_result && current_guess.total_count + lower_bound > best_of_all_guess.total_count {
-            continue;
+    fn beats(estimate: &Best, index: usize, best: &(Best, usize), objective: &dyn Objective) -> bool {
+        !best.0.has_result || match objective.compare(estimate, &best.0) {
+            Ordering::Less => true,
+            Ordering::Equal => index < best.1,
+            Ordering::Greater => false
         }
+    }
 
-        let mut sorted_groups: Vec<_> = groups.into_iter().collect();
-        sorted_groups.sort_unstable_by_key(|(_, g)| g.len()); 
+    // Once the best found by a better ranked guess meets the bound of the whole
+    // set, guesses ranked after it have nothing left to find.
+    fn settles(&self, index: usize, answers_bound: &Best, objective: &dyn Objective) -> bool {
+        let best = self.best.lock().unwrap();
+        best.0.has_result && best.1 <= index && objective.compare(&best.0, answers_bound) != Ordering::Greater
+    }
 
-        for (pattern, pattern_answers) in sorted_groups {
+    fn offer(&self, mut best: Best, index: usize, objective: &dyn Objective) {
+        best.finish();
+        let mut current = self.best.lock().unwrap();
+        if Self::beats(&best, index, &current, objective) {
+            self.score.store(objective.score(&best).to_bits(), AtomicOrdering::Relaxed);
+            *current = (best, index);
+        }
+    }
 
-            let sub_result = if Checker::is_success_pattern(pattern) {
-                Best {
-                    has_result: true,
-                    max_level: 0,
-                    total_count: 0,
-                    decision_tree: DecisionTree::new()
-                }
-            } else if pattern_answers.len() == 1{
-                Best {
-                    has_result: true,
-                    max_level: 1,
-                    total_count: 1,
-                    decision_tree: DecisionTree::from(pattern_answers.iter().nth(0).unwrap(), BTreeMap::from([(242, DecisionTree::new())]))
-                }
-            } else if pattern_answers.len() <= 3 {
-                let new_restrictions = Restriction::from(guess, pattern);
-                dfs_with_cache(current + 1, &pattern_answers, &pattern_answers, new_restrictions, cache, use_limit, counter)
-            } else {
-                let new_restrictions = Restriction::from(guess, pattern);
-                dfs_with_cache(current + 1, &pattern_answers, &filter_available_guesses(&new_restrictions, &availables), new_restrictions, cache, use_limit, counter)
-            };
-            
-            if !sub_result.has_result {
-                current_guess.has_result = false;
-                break
-            }
+    // The baseline when no guess reached it, e.g. it was found with a wider beam
+    // or the budget ran out first.
+    fn into_best(self, objective: &dyn Objective) -> Best {
+        let best = self.best.into_inner().unwrap().0;
+        if self.baseline.has_result && (!best.has_result || objective.compare(&self.baseline, &best) == Ordering::Less) {
+            self.baseline
+        } else {
+            best
+        }
+    }
+}
 
-            current_guess.update(pattern, sub_result);
+// Shared by reference between threads, one searcher can run parallel_wrapper's
+// tasks and the subtrees below them with one cache and one budget.
+pub struct Searcher<'a> {
+    pub table: &'a PatternTable,
+    pub config: SearchConfig,
+    pub cache: ShardedTable,
+    pub counter: Counter,
+    pub clock: Clock,
+    // Set once the budget cut a search short, its results are no longer the best in the beam.
+    pub truncated: AtomicBool,
+    // The root of the last exact search(0, ..) that ran to the end and its optimum.
+    pub solved: Mutex<Option<(StateKey, Best)>>
+}
 
-            let current_entropy = get_entropy(pattern, pattern_answers.len() as u32);
-            lower_bound -= current_entropy;
+impl<'a> Searcher<'a> {
+    pub fn new(table: &'a PatternTable, config: SearchConfig) -> Self {
+        let clock = Clock::start(&config.budget, config.observer.clone());
+        let cache = ShardedTable::new(config.cache_bytes);
+        Searcher {
+            table,
+            config,
+            cache,
+            counter: Counter::default(),
+            clock,
+            truncated: AtomicBool::new(false),
+            solved: Mutex::new(None)
+        }
+    }
 
-            if current_guess.total_count  + lower_bound > best_of_all_guess.total_count {
-                current_guess.has_result = false;
-                break
-            }
+    pub fn truncated(&self) -> bool {
+        self.truncated.load(AtomicOrdering::Relaxed)
+    }
+
+    // The counters so far, with no stages. Truncated once the clock ran out even
+    // if no search noticed, the wrappers skip work then.
+    pub fn stats(&self) -> SearchStats {
+        let load = |counters: &[AtomicU64; DEPTH_SLOTS]| counters.each_ref().map(|counter| counter.load(AtomicOrdering::Relaxed));
+        SearchStats {
+            nodes: self.counter.node_counter.load(AtomicOrdering::Relaxed),
+            beam_cuts: self.counter.beam_cut_counter.load(AtomicOrdering::Relaxed),
+            bound_prunes: self.counter.bound_prune_counter.load(AtomicOrdering::Relaxed),
+            cache_hits: load(&self.counter.cache_hit_counters),
+            cache_misses: load(&self.counter.cache_miss_counters),
+            stages: Vec::new(),
+            truncated: self.truncated() || self.clock.exhausted()
         }
+    }
 
-        if current_guess.has_result {
-            best_of_all_guess.better(current_guess);
+    // Starts from the configured cache file when it was written for the same
+    // word lists and settings.
+    pub fn load_cache(&mut self) {
+        if let Some(path) = &self.config.cache_path {
+            let fingerprint = TranspositionTable::fingerprint(self.table.dictionary(), &self.config);
+            self.cache = ShardedTable::load_or_new(path, fingerprint, self.config.cache_bytes);
         }
     }
 
-    cache  
-        .lock()
-        .unwrap()
-        .entry(restrictions)
-        .or_insert_with(BTreeMap::new)
-        .entry(availables.to_owned())
-        .or_insert_with(BTreeMap::new)
-        .insert(current, best_of_all_guess.clone());
+    pub fn save_cache(&self) -> io::Result<()> {
+        match &self.config.cache_path {
+            Some(path) => self.cache.save_to_path(path, TranspositionTable::fingerprint(self.table.dictionary(), &self.config)),
+            None => Ok(())
+        }
+    }
 
-    best_of_all_guess
-}
+    // Guesses still allowed by the game rules after `guess` showed `pattern`.
+    pub fn narrow(&self, guess: WordId, pattern: Pattern, availables: &BTreeSet<WordId>) -> BTreeSet<WordId> {
+        if self.config.hard_mode {
+            let restriction = Restriction::from(self.table.dictionary().word(guess), pattern);
+            filter_available_guesses(self.table.dictionary(), &restriction, availables)
+        } else {
+            availables.clone()
+        }
+    }
+
+    // Guesses worth trying next, small sets only try their own answers.
+    pub fn next_availables(&self, guess: WordId, pattern: Pattern, answers: &AnswerSet, availables: &BTreeSet<WordId>) -> BTreeSet<WordId> {
+        if !self.config.exact && answers.len() <= self.config.small_set_threshold {
+            answers.iter().collect()
+        } else {
+            self.narrow(guess, pattern, availables)
+        }
+    }
+
+    // rank_guesses, keeping guesses with the same partition when hard mode makes them differ.
+    pub fn rank(&self, answers: &AnswerSet, availables: &BTreeSet<WordId>, limit: usize) -> Vec<(WordId, f64, BTreeMap<Pattern, AnswerSet>)> {
+        let dedup = !(self.config.exact && self.config.hard_mode);
+        rank_guesses(self.table, answers, availables, limit, dedup, &*self.config.heuristic, self.config.priors.as_deref())
+    }
+
+    // Prior weight of a set of answers, Best::weighted_count sums it per guess.
+    pub fn weight(&self, answers: &AnswerSet) -> f64 {
+        match &self.config.priors {
+            Some(priors) => priors.mass(answers),
+            None => answers.len() as f64
+        }
+    }
+
+    // Best::init weighted by the priors.
+    pub fn init(&self, guess: WordId, answers: &AnswerSet) -> Best {
+        Best::init(guess, answers.len() as u32).with_weight(self.weight(answers))
+    }
+
+    // Best::single weighted by the priors.
+    pub fn single(&self, answers: &AnswerSet) -> Best {
+        Best::single(answers.first().unwrap(), self.table.word_len()).with_weight(self.weight(answers))
+    }
+
+    // Lower bound of any finished tree for a set of answers. Its weighted_count
+    // has the heaviest answer found by the next guess and the others by the one after.
+    fn bound(&self, answers: &AnswerSet) -> Best {
+        let max_level = get_lower_bound_level(answers.len(), self.table.word_len());
+        let heaviest = match &self.config.priors {
+            Some(priors) => priors.heaviest(answers),
+            None => 1.0
+        };
+        Best::bound(max_level, answers.len() as u32, 2.0 * self.weight(answers) - heaviest)
+    }
 
-pub fn dfs<'a>(current: u8, answers: &BTreeSet<&'a str>, availables: &BTreeSet<&'a str>) -> Best<'a> {
+    // Whether a guess ranked at `index`, with the branches in `current_guess` done
+    // and `remaining` bounding the others, can still beat its siblings.
+    fn can_improve(&self, current_guess: &Best, remaining: &Best, best: &SharedBest, index: usize) -> bool {
+        if !best.has_result() {
+            return true;
+        }
 
-    if current > MAX_TURNS {
-        return Best::new();
+        let mut estimate = remaining.clone();
+        estimate.merge(current_guess);
+        estimate.finish();
+        best.admits(&estimate, index, &*self.config.objective)
     }
 
-    let mut best_of_all_guess = Best::new();
+    // Admissible bound for `answers`: the best any guess could do if every group
+    // it leaves met its own bound.
+    pub fn lower_bound(&self, answers: &AnswerSet, availables: &BTreeSet<WordId>) -> Best {
+        let word_len = self.table.word_len();
+        let mut lower_bound = self.bound(answers);
+
+        let mut best_guess: Option<Best> = None;
+        for (guess, _, groups) in self.rank(answers, availables, usize::MAX) {
+            let mut bound = self.init(guess, answers);
+            for (pattern, pattern_answers) in groups.iter() {
+                if Checker::is_success_pattern(*pattern, word_len) {
+                    bound.merge(&Best::success());
+                } else {
+                    bound.merge(&self.bound(pattern_answers));
+                }
+            }
+            bound.finish();
 
-    let mut group_patterns = BTreeSet::<BTreeMap<u8, BTreeSet<&str>>>::new();
-    let mut preprocess_by_guess:Vec<_> = availables
-        .iter()
-        .filter_map(|guess| {
-            let (guess, entropy, groups) = get_entropy_sum(guess, &answers);
-            if group_patterns.contains(&groups) {
-                return None
+            if best_guess.as_ref().is_none_or(|best| self.config.objective.compare(&bound, best) == Ordering::Less) {
+                best_guess = Some(bound);
             }
-    
-            group_patterns.insert(groups.clone());
-
-            return Some((guess, entropy, groups))
-        })
-        .collect();
-    
-    preprocess_by_guess
-        .sort_by_cached_key(|(_, entropy, _)| *entropy);
-    
-    let length = preprocess_by_guess.len();
-    let top_guesses:Vec<_> = preprocess_by_guess
-        .into_iter()
-        .take(limit(length))
-        .collect();
-
-    for (guess, entropy, groups) in top_guesses {
-
-        let mut lower_bound = entropy;
-        let mut current_guess = Best::init(guess, answers.len() as u32);
-
-        if best_of_all_guess.has_result && current_guess.total_count + lower_bound > best_of_all_guess.total_count {
-            continue;
         }
 
-        let mut sorted_groups: Vec<_> = groups.into_iter().collect();
-        sorted_groups.sort_unstable_by_key(|(_, g)| g.len()); 
+        if let Some(mut bound) = best_guess {
+            if self.config.objective.compare(&bound, &lower_bound) == Ordering::Greater {
+                bound.decision_tree = Default::default();
+                lower_bound = bound;
+            }
+        }
+        lower_bound
+    }
 
-        for (pattern, pattern_answers) in sorted_groups {
+    // Checks a result of search(0, answers, availables, ..) from this config.
+    pub fn certify(&self, best: &Best, answers: &AnswerSet, availables: &BTreeSet<WordId>) -> Certificate {
+        let objective = &self.config.objective;
 
-            let sub_result = if Checker::is_success_pattern(pattern) {
-                Best {
-                    has_result: true,
-                    max_level: 0,
-                    total_count: 0,
-                    decision_tree: DecisionTree::new()
-                }
-            } else if pattern_answers.len() == 1{
-                Best {
-                    has_result: true,
-                    max_level: 1,
-                    total_count: 1,
-                    decision_tree: DecisionTree::from(pattern_answers.iter().nth(0).unwrap(), BTreeMap::from([(242, DecisionTree::new())]))
-                }
-            } else if pattern_answers.len() <= 3 {
-                dfs(current + 1, &pattern_answers, &pattern_answers)
-            } else {
-                let new_restrictions = Restriction::from(guess, pattern);
-                dfs(current + 1, &pattern_answers, &filter_available_guesses(&new_restrictions, &availables))
+        if !best.has_result {
+            return Certificate {
+                proven_optimal: false,
+                lower_bound: self.lower_bound(answers, availables),
+                gap: f64::INFINITY
             };
-            
-            if !sub_result.has_result {
-                current_guess.has_result = false;
-                break
+        }
+
+        // Only an exact search this searcher finished on the same root bounds the
+        // tree by the optimum it found.
+        let solved = self.solved.lock().unwrap().clone();
+        let lower_bound = match solved {
+            Some((root, optimum)) if root == StateKey::new(answers, availables) => optimum,
+            _ => self.lower_bound(answers, availables)
+        };
+
+        Certificate {
+            proven_optimal: objective.compare(best, &lower_bound) != Ordering::Greater,
+            gap: objective.score(best) - objective.score(&lower_bound),
+            lower_bound
+        }
+    }
+
+    // Best tree for `answers` with the guess numbered `current` (from 0) to play next.
+    pub fn search(&self, current: u8, answers: &AnswerSet, availables: &BTreeSet<WordId>) -> Best {
+        let best = self.search_node(current, answers, availables).0;
+        if current == 0 && self.config.exact && !self.truncated() && best.has_result {
+            let optimum = Best { decision_tree: Default::default(), ..best.clone() };
+            *self.solved.lock().unwrap() = Some((StateKey::new(answers, availables), optimum));
+        }
+        best
+    }
+
+    // search, and whether a beam cut guesses on the way so a wider one may do better.
+    fn search_node(&self, current: u8, answers: &AnswerSet, availables: &BTreeSet<WordId>) -> (Best, bool) {
+
+        if current >= self.config.max_turns {
+            return (Best::new(), false);
+        }
+
+        self.clock.tick();
+        self.counter.node_counter.fetch_add(1, AtomicOrdering::Relaxed);
+
+        let mut baseline = Best::new();
+
+        let key = self.config.use_cache.then(|| StateKey::new(answers, availables));
+        let remaining = self.config.max_turns - current;
+        // Approximate once other threads search at the same time.
+        let nodes_before = self.counter.node_counter.load(AtomicOrdering::Relaxed);
+        let shape = self.config.shape();
+
+        if let Some(key) = &key {
+            let depth = (current as usize).min(DEPTH_SLOTS - 1);
+            let lookup = self.cache.lookup(key, remaining, shape);
+            let counters = if matches!(lookup, Lookup::Result(..) | Lookup::NoResult) { &self.counter.cache_hit_counters } else { &self.counter.cache_miss_counters };
+            counters[depth].fetch_add(1, AtomicOrdering::Relaxed);
+
+            match lookup {
+                Lookup::Result(best, cut) => {
+                    self.counter.result_counter.fetch_add(1, AtomicOrdering::Relaxed);
+                    return (best, cut);
+                },
+                Lookup::NoResult => {
+                    self.counter.no_result_counter.fetch_add(1, AtomicOrdering::Relaxed);
+                    return (Best::new(), false);
+                },
+                Lookup::Baseline(best) => {
+                    self.counter.baseline_counter.fetch_add(1, AtomicOrdering::Relaxed);
+                    baseline = best;
+                },
+                Lookup::Miss => {}
             }
+        }
 
-            current_guess.update(pattern, sub_result);
+        let objective = &*self.config.objective;
+        let width = if self.config.exact { usize::MAX } else { self.config.beam.width(current) };
+        // One guess past the beam tells whether it cut any.
+        let mut top_guesses = self.rank(answers, availables, width.saturating_add(1));
+        let best_of_all_guess = SharedBest::new(baseline, objective);
+        if top_guesses.len() > width {
+            top_guesses.truncate(width);
+            self.counter.beam_cut_counter.fetch_add(1, AtomicOrdering::Relaxed);
+            best_of_all_guess.cut.store(true, AtomicOrdering::Relaxed);
+        }
+        let answers_bound = self.bound(answers);
 
-            let current_entropy = get_entropy(pattern, pattern_answers.len() as u32);
-            lower_bound -= current_entropy;
+        let try_guess = |(index, (guess, _, groups)): (usize, (WordId, f64, BTreeMap<Pattern, AnswerSet>))| {
 
-            if current_guess.total_count  + lower_bound > best_of_all_guess.total_count {
-                current_guess.has_result = false;
-                break
+            // Out of budget, keep the first complete tree.
+            if best_of_all_guess.has_result() && self.clock.exhausted() {
+                self.truncated.store(true, AtomicOrdering::Relaxed);
+                return;
             }
+
+            // Nothing beats guessing an answer that splits the rest into singletons.
+            if best_of_all_guess.settles(index, &answers_bound, objective) {
+                self.counter.bound_prune_counter.fetch_add(1, AtomicOrdering::Relaxed);
+                return;
+            }
+
+            self.try_guess(current, guess, groups, answers, availables, &best_of_all_guess, index);
+        };
+
+        if answers.len() >= self.config.parallel_threshold {
+            top_guesses.into_par_iter().enumerate().for_each(try_guess);
+        } else {
+            top_guesses.into_iter().enumerate().for_each(try_guess);
         }
 
-        if current_guess.has_result {
-            best_of_all_guess.better(current_guess);
+        let cut = best_of_all_guess.cut.load(AtomicOrdering::Relaxed);
+        let best_of_all_guess = best_of_all_guess.into_best(objective);
+
+        if let Some(key) = key.filter(|_| !self.truncated()) {
+            let cost = self.counter.node_counter.load(AtomicOrdering::Relaxed) - nodes_before;
+            self.cache.insert(key, remaining, best_of_all_guess.clone(), cost, cut.then_some(shape));
         }
+
+        (best_of_all_guess, cut)
     }
 
-    best_of_all_guess
-}
+    // Searches the groups `guess` leaves and offers the tree to its siblings'
+    // best unless a bound shows it cannot win.
+    #[allow(clippy::too_many_arguments)]
+    fn try_guess(&self, current: u8, guess: WordId, groups: BTreeMap<Pattern, AnswerSet>, answers: &AnswerSet, availables: &BTreeSet<WordId>, best: &SharedBest, index: usize) {
+        let word_len = self.table.word_len();
+        let mut current_guess = self.init(guess, answers);
 
+        let mut sorted_groups: Vec<_> = groups.into_iter().collect();
+        sorted_groups.sort_unstable_by_key(|(_, g)| g.len());
 
-fn basic_dfs<'a>(current: u8, answers: &BTreeSet<&'a str>, availables: &BTreeSet<&'a str>) -> Best<'a> {
-    let mut best_of_all_guess = Best::new();
-    for guess in availables {
+        // remaining_bounds[i] sums the bounds of the groups from i on.
+        let mut remaining_bounds = vec![Best::empty(); sorted_groups.len() + 1];
+        for (i, (pattern, pattern_answers)) in sorted_groups.iter().enumerate().rev() {
+            let mut bound = if Checker::is_success_pattern(*pattern, word_len) {
+                Best::success()
+            } else {
+                self.bound(pattern_answers)
+            };
+            bound.merge(&remaining_bounds[i + 1]);
+            remaining_bounds[i] = bound;
+        }
 
-        let groups = group_by_pattern(guess, &answers);
-        
-        if groups.len() == 1 && !groups.contains_key(&242) {
-            continue
+        if !self.can_improve(&current_guess, &remaining_bounds[0], best, index) {
+            self.counter.bound_prune_counter.fetch_add(1, AtomicOrdering::Relaxed);
+            return;
         }
 
-        let mut current_guess = Best::init(guess, answers.len() as u32);
+        for (i, (pattern, pattern_answers)) in sorted_groups.into_iter().enumerate() {
 
-        for (pattern, pattern_answers) in  groups {
-            let sub_result = if Checker::is_success_pattern(pattern) {
-                Best {
-                    has_result: true,
-                    max_level: 0,
-                    total_count: 0,
-                    decision_tree: DecisionTree::new()
-                }
+            let sub_result = if Checker::is_success_pattern(pattern, word_len) {
+                Best::success()
+            } else if pattern_answers.len() == 1 {
+                self.single(&pattern_answers)
             } else {
-                let new_restrictions = Restriction::from(guess, pattern);
-                basic_dfs(current + 1, &pattern_answers, &filter_available_guesses(&new_restrictions, &availables))
-            };   
+                let new_availables = self.next_availables(guess, pattern, &pattern_answers, availables);
+                let (sub_result, cut) = self.search_node(current + 1, &pattern_answers, &new_availables);
+                if cut {
+                    best.cut.store(true, AtomicOrdering::Relaxed);
+                }
+                sub_result
+            };
+
+            if !sub_result.has_result {
+                return;
+            }
+
             current_guess.update(pattern, sub_result);
+
+            if !self.can_improve(&current_guess, &remaining_bounds[i + 1], best, index) {
+                self.counter.bound_prune_counter.fetch_add(1, AtomicOrdering::Relaxed);
+                return;
+            }
         }
-        best_of_all_guess.better(current_guess);
-    }
 
-    best_of_all_guess
-}
\ No newline at end of file
+        best.offer(current_guess, index, &*self.config.objective);
+    }
+}
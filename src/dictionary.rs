@@ -0,0 +1,116 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use crate::common::WordListError;
+use crate::answer_set::AnswerSet;
+use crate::utils::{load_word_lists, word_length};
+use crate::encoding::{FNV_OFFSET, fnv1a};
+
+// Index of a word in its Dictionary. Answers take the ids 0..answer_count,
+// so an answer id is also its column in the PatternTable.
+pub type WordId = u16;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnknownWord(pub String);
+
+impl fmt::Display for UnknownWord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown word {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownWord {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dictionary {
+    word_len: usize,
+    answer_count: usize,
+    words: Vec<String>,
+    ids: HashMap<String, WordId>
+}
+
+impl Dictionary {
+    // Answers are guessable too, words missing from the guess list are added after them.
+    pub fn new(answers: &BTreeSet<&str>, words: &BTreeSet<&str>) -> Self {
+        let words: Vec<String> = answers
+            .iter()
+            .chain(words.difference(answers))
+            .map(|word| word.to_string())
+            .collect();
+
+        assert!(words.len() <= WordId::MAX as usize, "Too many words for WordId.");
+        assert!(answers.len() <= AnswerSet::CAPACITY, "Too many answers for AnswerSet.");
+
+        Dictionary {
+            word_len: word_length(answers),
+            answer_count: answers.len(),
+            ids: words.iter().enumerate().map(|(i, word)| (word.clone(), i as WordId)).collect(),
+            words
+        }
+    }
+
+    pub fn from_lists(answers: &str, words: &str) -> Result<Self, WordListError> {
+        let (answers, words) = load_word_lists(answers, words)?;
+        Ok(Dictionary::new(&answers, &words))
+    }
+
+    pub fn word_len(&self) -> usize {
+        self.word_len
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn answer_count(&self) -> usize {
+        self.answer_count
+    }
+
+    pub fn is_answer(&self, id: WordId) -> bool {
+        (id as usize) < self.answer_count
+    }
+
+    pub fn word(&self, id: WordId) -> &str {
+        &self.words[id as usize]
+    }
+
+    pub fn id(&self, word: &str) -> Result<WordId, UnknownWord> {
+        self.ids.get(word).cloned().ok_or_else(|| UnknownWord(word.to_string()))
+    }
+
+    pub fn ids<'a, I: IntoIterator<Item = &'a str>>(&self, words: I) -> Result<BTreeSet<WordId>, UnknownWord> {
+        words.into_iter().map(|word| self.id(word)).collect()
+    }
+
+    pub fn words<'a, I: IntoIterator<Item = &'a WordId>>(&self, ids: I) -> Vec<&str> {
+        ids.into_iter().map(|id| self.word(*id)).collect()
+    }
+
+    pub fn answers(&self) -> AnswerSet {
+        AnswerSet::full(self.answer_count)
+    }
+
+    pub fn guesses(&self) -> BTreeSet<WordId> {
+        (0..self.words.len() as WordId).collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.words.iter().map(|word| word.as_str())
+    }
+
+    // Identifies the word lists, files built from them store it in their header.
+    pub fn checksum(&self) -> u64 {
+        let mut hash = FNV_OFFSET;
+        let answer_count = self.answer_count.to_string();
+        for word in self.iter().chain([answer_count.as_str()]) {
+            for byte in word.bytes().chain([b'\n']) {
+                hash = fnv1a(hash, byte as u64);
+            }
+        }
+        hash
+    }
+}
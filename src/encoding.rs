@@ -0,0 +1,57 @@
+use std::io::{self, Read};
+
+pub const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+// One FNV-1a step. Values wider than a byte go in whole, which is all the
+// checksums and fingerprints here need.
+pub fn fnv1a(hash: u64, value: u64) -> u64 {
+    (hash ^ value).wrapping_mul(FNV_PRIME)
+}
+
+// Hashes written to disk, so unlike std's hashers they stay the same across builds.
+pub struct StableHasher(pub u64);
+
+impl StableHasher {
+    pub fn new(salt: u64) -> Self {
+        StableHasher(FNV_OFFSET ^ salt)
+    }
+
+    pub fn add(&mut self, value: u64) {
+        self.0 = fnv1a(self.0, value);
+        self.0 ^= self.0 >> 29;
+    }
+
+    pub fn add_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.add(*byte as u64);
+        }
+        self.add(u64::MAX);
+    }
+}
+
+// Little endian readers for the binary files: solver caches, checkpoints,
+// pattern tables and decision trees.
+pub fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut bytes = [0u8; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+pub fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0u8; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+pub fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
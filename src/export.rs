@@ -0,0 +1,122 @@
+use std::fmt::Write as _;
+
+use crate::common::{DecisionTree, Pattern};
+use crate::game::Checker;
+use crate::table::PatternTable;
+use crate::dictionary::WordId;
+use crate::answer_set::AnswerSet;
+
+// What part of a tree to_dot and to_mermaid draw.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportOptions {
+    // Levels of edges drawn below the first node, 0 draws it alone. Every level when None.
+    pub max_depth: Option<usize>,
+    // Moves from the root to the subtree drawn, the whole tree when empty.
+    pub path: Vec<(WordId, Pattern)>,
+    // Label edges with coloured squares instead of B, Y and G letters.
+    pub squares: bool
+}
+
+// Feedback as "BYGBB" or "⬛🟨🟩⬛⬛", first letter first.
+pub fn pattern_label(pattern: Pattern, word_len: usize, squares: bool) -> String {
+    let marks = if squares { ["⬛", "🟨", "🟩"] } else { ["B", "Y", "G"] };
+    Checker::format_pattern(pattern, word_len).chars().map(|digit| marks[digit as usize - '0' as usize]).collect()
+}
+
+struct Node {
+    label: String,
+    // Has branches below the depth limit that are not drawn.
+    truncated: bool
+}
+
+// The drawn part of a tree, shared by both formats. Wins are not drawn, a
+// node's count includes the answer its own guess finds.
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<(usize, usize, String)>
+}
+
+impl Graph {
+    fn add(&mut self, tree: &DecisionTree, answers: &AnswerSet, table: &PatternTable, options: &ExportOptions, depth: usize) -> usize {
+        let dictionary = table.dictionary();
+        let word_len = table.word_len();
+        let id = self.nodes.len();
+
+        let guess = tree.guess.map_or("?", |guess| dictionary.word(guess));
+        self.nodes.push(Node { label: format!("{}\n{}", guess, answers.len()), truncated: false });
+
+        let Some(guess) = tree.guess else {
+            return id;
+        };
+
+        let branches: Vec<_> = answers.partition(table.row(guess)).into_iter()
+            .filter(|(pattern, _)| !Checker::is_success_pattern(*pattern, word_len))
+            .filter_map(|(pattern, pattern_answers)| tree.branch.get(&pattern).map(|subtree| (pattern, subtree, pattern_answers)))
+            .collect();
+
+        if options.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            self.nodes[id].truncated = !branches.is_empty();
+            return id;
+        }
+
+        for (pattern, subtree, pattern_answers) in branches {
+            let child = self.add(subtree, &pattern_answers, table, options, depth + 1);
+            self.edges.push((id, child, pattern_label(pattern, word_len, options.squares)));
+        }
+        id
+    }
+
+    fn new(tree: &DecisionTree, answers: &AnswerSet, table: &PatternTable, options: &ExportOptions) -> Result<Self, String> {
+        let dictionary = table.dictionary();
+        let (mut node, mut answers) = (tree, answers.clone());
+
+        for (guess, pattern) in options.path.iter() {
+            if node.guess != Some(*guess) {
+                return Err(format!("the tree does not play {} there", dictionary.word(*guess)));
+            }
+            node = node.branch.get(pattern).ok_or_else(|| {
+                format!("the tree has no branch for {} after {}", pattern_label(*pattern, table.word_len(), false), dictionary.word(*guess))
+            })?;
+            answers = answers.filter(table.row(*guess), *pattern);
+        }
+
+        let mut graph = Graph::default();
+        graph.add(node, &answers, table, options, 0);
+        Ok(graph)
+    }
+}
+
+// Graphviz source, one box per guess labelled with the guess and its answer count.
+// Nodes cut off by the depth limit are dashed.
+pub fn to_dot(tree: &DecisionTree, answers: &AnswerSet, table: &PatternTable, options: &ExportOptions) -> Result<String, String> {
+    let graph = Graph::new(tree, answers, table, options)?;
+    let mut dot = String::from("digraph tree {\n    node [shape=box, fontname=\"monospace\"];\n");
+
+    for (id, node) in graph.nodes.iter().enumerate() {
+        let style = if node.truncated { ", style=dashed" } else { "" };
+        let _ = writeln!(dot, "    n{} [label=\"{}\"{}];", id, node.label.replace('\n', "\\n"), style);
+    }
+    for (from, to, label) in graph.edges.iter() {
+        let _ = writeln!(dot, "    n{} -> n{} [label=\"{}\"];", from, to, label);
+    }
+
+    dot.push_str("}\n");
+    Ok(dot)
+}
+
+// A Mermaid flowchart of the same graph, cut off nodes end in an ellipsis.
+pub fn to_mermaid(tree: &DecisionTree, answers: &AnswerSet, table: &PatternTable, options: &ExportOptions) -> Result<String, String> {
+    let graph = Graph::new(tree, answers, table, options)?;
+    let mut mermaid = String::from("flowchart TD\n");
+
+    for (id, node) in graph.nodes.iter().enumerate() {
+        let more = if node.truncated { " …" } else { "" };
+        let _ = writeln!(mermaid, "    n{}[\"{}{}\"]", id, node.label.replace('\n', "<br/>"), more);
+    }
+    for (from, to, label) in graph.edges.iter() {
+        let _ = writeln!(mermaid, "    n{} -->|\"{}\"| n{}", from, label, to);
+    }
+
+    Ok(mermaid)
+}
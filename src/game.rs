@@ -1,13 +1,17 @@
 use std::cmp::max;
 use std::collections::{BTreeMap, BTreeSet};
-use crate::common::{Restriction, DecisionTree};
-use crate::utils::{filter_available_guesses, stat_color};
+use crate::common::{DecisionTree, Pattern};
+use crate::table::PatternTable;
+use crate::dictionary::WordId;
+use crate::answer_set::AnswerSet;
+use crate::prior::Priors;
+use crate::validate::{validate, ValidationReport};
 
 pub struct Checker {
 }
 
 impl Checker {
-    pub fn check(target: &str, guess: &str) -> u8 {
+    pub fn check(target: &str, guess: &str) -> Pattern {
         let mut freq = BTreeMap::<char, usize>::new();
         for (guess_c, target_c) in guess.chars().zip(target.chars()) {
             if guess_c != target_c {
@@ -16,8 +20,8 @@ impl Checker {
             }
         }
 
-        let mut pattern: u8 = 0;
-        let mut base: u8 = 1;
+        let mut pattern: Pattern = 0;
+        let mut base: Pattern = 1;
         for (guess_c, target_c) in guess.chars().zip(target.chars()) {
             if guess_c == target_c {
                 pattern += 2 * base;
@@ -35,71 +39,124 @@ impl Checker {
         pattern
     }
 
-    pub fn is_success_pattern(pattern: u8) -> bool {
-        pattern == 242
+    // One digit per letter as check builds them: 0 grey, 1 yellow, 2 green.
+    pub fn parse_pattern(digits: &str) -> Option<Pattern> {
+        digits.chars().rev().try_fold(0 as Pattern, |pattern, digit| {
+            digit.to_digit(3).map(|digit| pattern * 3 + digit as Pattern)
+        })
+    }
+
+    // The digits parse_pattern reads, one per letter.
+    pub fn format_pattern(pattern: Pattern, word_len: usize) -> String {
+        (0..word_len).scan(pattern, |rest, _| {
+            let digit = (*rest % 3) as u8;
+            *rest /= 3;
+            Some((b'0' + digit) as char)
+        }).collect()
+    }
+
+    // Number of distinct patterns for words of this length, 3^word_len.
+    pub fn pattern_count(word_len: usize) -> usize {
+        3_usize.pow(word_len as u32)
+    }
+
+    // All green, e.g. 242 for five letter words.
+    pub fn success_pattern(word_len: usize) -> Pattern {
+        (Checker::pattern_count(word_len) - 1) as Pattern
+    }
+
+    pub fn is_success_pattern(pattern: Pattern, word_len: usize) -> bool {
+        pattern == Checker::success_pattern(word_len)
     }
 }
 
 pub struct Solver<'a> {
-    // decision_tree: DecisionTree<'a>,
-    current: &'a DecisionTree<'a>
+    // decision_tree: DecisionTree,
+    current: &'a DecisionTree
 }
 
-impl<'a> Solver<'a> {
-    pub fn guess(&self) -> String {
-        self.current.guess()
+impl Solver<'_> {
+    pub fn guess(&self) -> WordId {
+        self.current.guess().unwrap()
     }
 }
 
 pub struct Evaluator<'a> {
-    pub answers: &'a BTreeSet<&'a str>,
-    pub words: &'a BTreeSet<&'a str>
+    pub answers: &'a AnswerSet,
+    pub words: &'a BTreeSet<WordId>,
+    pub table: &'a PatternTable,
+    // Weighs the averages, every answer counts the same when None.
+    pub priors: Option<&'a Priors>
+}
+
+// What a tree did over every answer, averages weighted by the Evaluator's priors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    pub total: u32,
+    pub max_turn: u32,
+    pub average: f64,
+    // Expected guesses with answers drawn by the priors, average without them.
+    pub weighted_average: f64,
+    // Per answer the guesses the tree played and the patterns they showed.
+    pub plays: Vec<(WordId, Vec<(WordId, Pattern)>)>
 }
 
-impl<'a> Evaluator<'_> {
-    pub fn evaluate(&self, decision_tree: DecisionTree, is_hard:bool) {
+impl Evaluator<'_> {
+    // Every problem that would stop evaluate, and answers needing more than
+    // `turn_cap` guesses, see validate.
+    pub fn validate(&self, decision_tree: &DecisionTree, is_hard: bool, turn_cap: u32) -> ValidationReport {
+        validate(decision_tree, self.answers, self.words, self.table, is_hard, turn_cap)
+    }
+
+    // Plays the tree for every answer, however deep it goes. A tree that cannot be
+    // played through, see ValidationReport::is_playable, is an error with its problems.
+    pub fn evaluate(&self, decision_tree: DecisionTree, is_hard:bool) -> Result<Evaluation, ValidationReport> {
+        let report = self.validate(&decision_tree, is_hard, u32::MAX);
+        if !report.is_playable() {
+            return Err(report);
+        }
+
+        let dictionary = self.table.dictionary();
         let mut total = 0;
         let mut max_turn = 0;
+        let (mut weighted_total, mut total_weight) = (0.0, 0.0);
+        let mut plays = Vec::new();
         for answer in self.answers.iter() {
-
-            println!("============{}============", answer);
+            let mut played = Vec::new();
 
             let mut solver = Solver {
                 current: &decision_tree
             };
-            
-            let mut allowed = self.words.clone();
-            let mut restrictions = Restriction::new();
 
             let mut turns = 0;
             loop {
                 let guess = solver.guess();
-                
-                assert_eq!(allowed.contains(guess.as_str()), true);
+                let pattern = self.table.get(guess, answer);
+                played.push((guess, pattern));
 
-                let pattern = Checker::check(answer, &guess);
-
-                println!("{}: {}", turns, stat_color(&guess, pattern));
-                
                 turns += 1;
-                if Checker::is_success_pattern(pattern) {
+                if Checker::is_success_pattern(pattern, dictionary.word_len()) {
                     break;
                 }
 
                 solver.current = DecisionTree::next(solver.current, pattern);
-
-                if is_hard {
-                    restrictions = restrictions.merge(&Restriction::from(&guess, pattern));
-                    allowed = filter_available_guesses(&restrictions, &allowed);
-                }
-
-                assert!(turns < 10, "No answer less than 10.");
             }
 
+            let weight = self.priors.map_or(1.0, |priors| priors.weight(answer));
+            weighted_total += weight * turns as f64;
+            total_weight += weight;
+
             total += turns;
-            max_turn = max(max_turn, turns)
+            max_turn = max(max_turn, turns);
+            plays.push((answer, played));
         }
 
-        println!("Total: {}, Avg: {}, Max: {}", total, total as f32 / self.answers.len() as f32, max_turn);
+        Ok(Evaluation {
+            total,
+            max_turn,
+            average: total as f64 / self.answers.len() as f64,
+            weighted_average: weighted_total / total_weight,
+            plays
+        })
     }
 }
\ No newline at end of file
@@ -0,0 +1,144 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::Ordering as AtomicOrdering;
+use std::time::{Duration, Instant};
+
+use crate::common::Best;
+use crate::config::SearchConfig;
+use crate::dfs::Searcher;
+use crate::table::PatternTable;
+use crate::dictionary::WordId;
+use crate::answer_set::AnswerSet;
+use crate::utils::{PatternCounter, get_entropy};
+use crate::wrapper::start_word_search;
+
+// Orders the guesses the beam tries, lower scores first. A heuristic only sees
+// how a guess splits the answers, the counter holds its bucket sizes and their
+// prior masses. A mass is the bucket size when answers are equally likely.
+pub trait GuessHeuristic: fmt::Debug + Send + Sync {
+    // Stable identifier, used on the command line and in fingerprints.
+    fn name(&self) -> String;
+
+    fn score(&self, buckets: &PatternCounter, word_len: usize) -> f64;
+}
+
+// get_entropy summed over the buckets: 2n - 1 per bucket that is not a win.
+// Weighted, 2n - 1 times the bucket's mean answer weight.
+#[derive(Debug, Clone, Default)]
+pub struct CurrentScore;
+
+impl GuessHeuristic for CurrentScore {
+    fn name(&self) -> String {
+        "score".to_string()
+    }
+
+    fn score(&self, buckets: &PatternCounter, word_len: usize) -> f64 {
+        buckets.weighted().map(|(pattern, count, mass)| {
+            get_entropy(pattern, word_len, count) as f64 * mass / count as f64
+        }).sum()
+    }
+}
+
+// Most information in bits, negated.
+#[derive(Debug, Clone, Default)]
+pub struct ShannonEntropy;
+
+impl GuessHeuristic for ShannonEntropy {
+    fn name(&self) -> String {
+        "entropy".to_string()
+    }
+
+    fn score(&self, buckets: &PatternCounter, _word_len: usize) -> f64 {
+        let total: f64 = buckets.weighted().map(|(_, _, mass)| mass).sum();
+        buckets.weighted().filter(|(_, _, mass)| *mass > 0.0).map(|(_, _, mass)| {
+            let p = mass / total;
+            p * p.log2()
+        }).sum()
+    }
+}
+
+// Expected size of the set left after the guess, answers drawn by their priors.
+#[derive(Debug, Clone, Default)]
+pub struct ExpectedSize;
+
+impl GuessHeuristic for ExpectedSize {
+    fn name(&self) -> String {
+        "expected".to_string()
+    }
+
+    fn score(&self, buckets: &PatternCounter, _word_len: usize) -> f64 {
+        let total: f64 = buckets.weighted().map(|(_, _, mass)| mass).sum();
+        buckets.weighted().map(|(_, count, mass)| count as f64 * mass).sum::<f64>() / total
+    }
+}
+
+// Lightest heaviest bucket, the smallest worst case when answers are equally likely.
+#[derive(Debug, Clone, Default)]
+pub struct LargestBucket;
+
+impl GuessHeuristic for LargestBucket {
+    fn name(&self) -> String {
+        "max".to_string()
+    }
+
+    fn score(&self, buckets: &PatternCounter, _word_len: usize) -> f64 {
+        buckets.weighted().map(|(_, _, mass)| mass).fold(0.0, f64::max)
+    }
+}
+
+// Most non-empty buckets, negated.
+#[derive(Debug, Clone, Default)]
+pub struct MostParts;
+
+impl GuessHeuristic for MostParts {
+    fn name(&self) -> String {
+        "parts".to_string()
+    }
+
+    fn score(&self, buckets: &PatternCounter, _word_len: usize) -> f64 {
+        -(buckets.iter().count() as f64)
+    }
+}
+
+pub fn all_heuristics() -> Vec<Arc<dyn GuessHeuristic>> {
+    vec![Arc::new(CurrentScore), Arc::new(ShannonEntropy), Arc::new(ExpectedSize), Arc::new(LargestBucket), Arc::new(MostParts)]
+}
+
+// "score", "entropy", "expected", "max" or "parts".
+pub fn parse_heuristic(s: &str) -> Result<Arc<dyn GuessHeuristic>, String> {
+    all_heuristics()
+        .into_iter()
+        .find(|heuristic| heuristic.name() == s)
+        .ok_or_else(|| format!("unknown heuristic {:?}", s))
+}
+
+// How one heuristic did in compare_heuristics.
+#[derive(Debug, Clone)]
+pub struct HeuristicReport {
+    pub name: String,
+    pub best: Best,
+    pub nodes: u64,
+    pub elapsed: Duration
+}
+
+// Solves the same answers once per heuristic with everything else from `config`,
+// from `start_word` when given and from scratch otherwise.
+pub fn compare_heuristics(heuristics: &[Arc<dyn GuessHeuristic>], start_word: Option<WordId>, answers: &AnswerSet, availables: &BTreeSet<WordId>, table: &PatternTable, config: &SearchConfig) -> Vec<HeuristicReport> {
+    heuristics.iter().map(|heuristic| {
+        let searcher = Searcher::new(table, SearchConfig { heuristic: heuristic.clone(), ..config.clone() });
+        let started = Instant::now();
+
+        let best = match start_word {
+            Some(start_word) => start_word_search(&searcher, start_word, answers, availables),
+            None => searcher.search(0, answers, availables)
+        };
+
+        HeuristicReport {
+            name: heuristic.name(),
+            best,
+            nodes: searcher.counter.node_counter.load(AtomicOrdering::Relaxed),
+            elapsed: started.elapsed()
+        }
+    }).collect()
+}
@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::io;
+use std::path::PathBuf;
+
+use rayon::prelude::*;
+
+use crate::common::Best;
+use crate::config::SearchConfig;
+use crate::dfs::Searcher;
+use crate::table::PatternTable;
+use crate::dictionary::{Dictionary, WordId};
+use crate::answer_set::AnswerSet;
+use crate::transposition::{StateKey, TranspositionTable};
+use crate::encoding::StableHasher;
+use crate::checkpoint::Checkpoint;
+use crate::utils::PatternCounter;
+use crate::heuristic::GuessHeuristic;
+use crate::prior::Priors;
+use crate::wrapper::{parallel_wrapper, start_word_search};
+
+// Results by hard mode and opener.
+type Results = BTreeMap<(bool, WordId), Best>;
+
+// Every guess with the score rank_guesses sorts it by, best first. Only bucket
+// sizes are counted, so the whole guess list takes a moment.
+pub fn opener_scores(table: &PatternTable, answers: &AnswerSet, availables: &BTreeSet<WordId>, heuristic: &dyn GuessHeuristic, priors: Option<&Priors>) -> Vec<(WordId, f64)> {
+    let word_len = table.word_len();
+    let answer_ids: Vec<WordId> = answers.iter().collect();
+    let mut counter = PatternCounter::new(word_len);
+
+    let mut scores: Vec<_> = availables.iter().map(|guess| {
+        counter.count(table.row(*guess), &answer_ids, priors);
+        (*guess, heuristic.score(&counter, word_len))
+    }).collect();
+
+    scores.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+    scores
+}
+
+// One opener's results, trees dropped. A Best without a result means no tree
+// fit in max_turns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standing {
+    pub word: WordId,
+    // opener_scores of the word.
+    pub score: f64,
+    pub hard: Best,
+    pub normal: Best
+}
+
+impl Standing {
+    // Both results, the one for `hard_mode` first.
+    fn by_mode(&self, hard_mode: bool) -> (&Best, &Best) {
+        if hard_mode { (&self.hard, &self.normal) } else { (&self.normal, &self.hard) }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leaderboard {
+    pub answer_count: usize,
+    // Ranked by the configured mode's result under the objective, then the other mode's.
+    pub standings: Vec<Standing>
+}
+
+impl Leaderboard {
+    // Tab separated, one opener per line below a header.
+    pub fn to_tsv(&self, dictionary: &Dictionary) -> String {
+        let mut tsv = String::from("rank\tword\tscore\thard_total\thard_average\thard_worst\tnormal_total\tnormal_average\tnormal_worst\n");
+        let summary = |best: &Best| if best.has_result {
+            format!("{}\t{:.4}\t{}", best.total_count, best.total_count as f64 / self.answer_count as f64, best.max_level)
+        } else {
+            "-\t-\t-".to_string()
+        };
+
+        for (rank, standing) in self.standings.iter().enumerate() {
+            let _ = writeln!(tsv, "{}\t{}\t{}\t{}\t{}", rank + 1, dictionary.word(standing.word), standing.score, summary(&standing.hard), summary(&standing.normal));
+        }
+        tsv
+    }
+}
+
+// Runs the `top` openers by opener_scores with the configured heuristic in hard
+// and normal mode and ranks them.
+#[derive(Debug, Clone, Default)]
+pub struct Sweep {
+    pub top: usize,
+    // parallel_wrapper per opener instead of start_word_search on a cache shared by all of them.
+    pub parallel: bool,
+    // Checkpoint the finished openers are appended to and skipped from when the sweep runs again.
+    pub checkpoint_path: Option<PathBuf>
+}
+
+impl Sweep {
+    // What a recorded result depends on, for both modes.
+    fn fingerprint(&self, answers: &AnswerSet, availables: &BTreeSet<WordId>, table: &PatternTable, config: &SearchConfig) -> u64 {
+        let key = StateKey::new(answers, availables);
+        let mut hasher = StableHasher::new(0);
+        for hard_mode in [true, false] {
+            hasher.add(TranspositionTable::fingerprint(table.dictionary(), &SearchConfig { hard_mode, ..config.clone() }));
+        }
+        hasher.add(config.shape());
+        hasher.add(config.small_set_threshold as u64);
+        hasher.add(self.parallel as u64);
+        hasher.add(key.hash);
+        hasher.add(key.check);
+        hasher.0
+    }
+
+    // A cache file holds one mode, the sweep runs both so it does not take one.
+    pub fn run(&self, answers: &AnswerSet, availables: &BTreeSet<WordId>, table: &PatternTable, config: &SearchConfig) -> io::Result<Leaderboard> {
+        if config.cache_path.is_some() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "--cache-file does not apply to the leaderboard"));
+        }
+        let openers: Vec<_> = opener_scores(table, answers, availables, &*config.heuristic, config.priors.as_deref()).into_iter().take(self.top).collect();
+
+        let (checkpoint, mut done): (Option<Checkpoint<(bool, WordId)>>, Results) = match &self.checkpoint_path {
+            Some(path) => {
+                let (checkpoint, done) = Checkpoint::open(path, self.fingerprint(answers, availables, table, config))?;
+                (Some(checkpoint), done)
+            },
+            None => (None, BTreeMap::new())
+        };
+
+        for hard_mode in [true, false] {
+            // parallel_wrapper checkpoints are per opener, the sweep keeps its own.
+            let mode_config = SearchConfig { hard_mode, checkpoint_path: None, ..config.clone() };
+            // Openers share one cache per mode, their subtrees keep meeting the same sets.
+            // The budget is spent by the whole mode rather than per opener.
+            let searcher = Searcher::new(table, SearchConfig { use_cache: true, ..mode_config.clone() });
+
+            let results = openers.par_iter().filter(|(word, _)| !done.contains_key(&(hard_mode, *word))).map(|(word, _)| {
+                let (best, complete) = if self.parallel {
+                    let (best, stats) = parallel_wrapper(*word, answers, availables, table, &mode_config)?;
+                    (best, !stats.truncated)
+                } else {
+                    let best = start_word_search(&searcher, *word, answers, availables);
+                    (best, !searcher.clock.exhausted())
+                };
+                let best = Best { decision_tree: Default::default(), ..best };
+
+                // Results cut short by the budget are left for a later sweep.
+                if let Some(checkpoint) = checkpoint.as_ref().filter(|_| complete) {
+                    checkpoint.record(&(hard_mode, *word), &best)?;
+                }
+                Ok(((hard_mode, *word), best))
+            }).collect::<io::Result<Vec<_>>>()?;
+
+            done.extend(results);
+        }
+
+        let objective = &config.objective;
+        // Openers with no tree rank last.
+        let compare = |a: &Best, b: &Best| match (a.has_result, b.has_result) {
+            (true, true) => objective.compare(a, b),
+            (a, b) => b.cmp(&a)
+        };
+
+        let mut standings: Vec<_> = openers.into_iter().map(|(word, score)| Standing {
+            word,
+            score,
+            hard: done.remove(&(true, word)).unwrap_or_else(Best::new),
+            normal: done.remove(&(false, word)).unwrap_or_else(Best::new)
+        }).collect();
+
+        standings.sort_by(|a, b| {
+            let ((a_first, a_second), (b_first, b_second)) = (a.by_mode(config.hard_mode), b.by_mode(config.hard_mode));
+            compare(a_first, b_first).then_with(|| compare(a_second, b_second))
+        });
+
+        Ok(Leaderboard {
+            answer_count: answers.len(),
+            standings
+        })
+    }
+}
@@ -1,19 +1,166 @@
 
-use std::collections::BTreeSet;
-
 pub mod game;
 pub mod utils;
 pub mod common;
 pub mod wrapper;
 pub mod dfs;
+pub mod table;
+pub mod dictionary;
+pub mod answer_set;
+pub mod config;
+pub mod objective;
+pub mod transposition;
+pub mod encoding;
+pub mod checkpoint;
+pub mod shard;
+pub mod leaderboard;
+pub mod heuristic;
+pub mod strategy;
+pub mod prior;
+pub mod validate;
+pub mod tree_file;
+pub mod export;
+pub mod sheet;
+pub mod observer;
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+
+use game::{Evaluator, Evaluation};
+use wrapper::{start_word_wrapper, parallel_wrapper, baseline_wrapper};
+use table::PatternTable;
+use dictionary::{Dictionary, WordId};
+use common::{Best, DecisionTree, Pattern};
+use answer_set::AnswerSet;
+use config::SearchConfig;
+use dfs::Searcher;
+use shard::Manifest;
+use leaderboard::Sweep;
+use heuristic::{all_heuristics, compare_heuristics};
+use strategy::{Greedy, Strategy};
+use game::Checker;
+use utils::{filter_available_answers, load_word_list, stat_color};
+use prior::{Priors, parse_weights, likely_answers};
+use validate::validate;
+use export::{ExportOptions, to_dot, to_mermaid};
+use observer::{Event, Observer, SearchStats};
+use sheet::{SheetOptions, cheat_sheet};
+
+
+// Removes `name` and the value after it from the arguments.
+fn take_flag(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == name)?;
+    let value = args.get(index + 1).cloned().unwrap_or_else(|| panic!("missing value for {}", name));
+    args.drain(index..index + 2);
+    Some(value)
+}
+
+// "salet:00120", a guess and the pattern it showed.
+fn parse_move(dictionary: &Dictionary, played: &str) -> (WordId, Pattern) {
+    let (word, digits) = played.split_once(':').expect("moves look like salet:00120");
+    let guess = dictionary.id(word).unwrap();
+    let pattern = Checker::parse_pattern(digits).expect("patterns are one 0, 1 or 2 per letter");
+    (guess, pattern)
+}
+
+// Reports a problem with the command line and exits, instead of panicking.
+fn fail(message: impl fmt::Display) -> ! {
+    eprintln!("{}", message);
+    process::exit(2)
+}
+
+// "--likely-answers N", at least one answer and no more than an AnswerSet holds.
+fn parse_likely_answers(limit: &str) -> Result<usize, String> {
+    match limit.parse::<usize>() {
+        Ok(limit) if (1..=AnswerSet::CAPACITY).contains(&limit) => Ok(limit),
+        Ok(_) => Err(format!("--likely-answers must be between 1 and {}", AnswerSet::CAPACITY)),
+        Err(_) => Err(format!("invalid --likely-answers {:?}", limit))
+    }
+}
 
-use game::Evaluator;
-use wrapper::start_word_wrapper;
+// Each answer's game, then the totals.
+fn print_evaluation(dictionary: &Dictionary, evaluation: &Evaluation, weighted: bool) {
+    for (answer, played) in evaluation.plays.iter() {
+        println!("============{}============", dictionary.word(*answer));
+        for (turn, (guess, pattern)) in played.iter().enumerate() {
+            println!("{}: {}", turn, stat_color(dictionary.word(*guess), *pattern));
+        }
+    }
 
+    if weighted {
+        println!("Total: {}, Avg: {}, Weighted Avg: {}, Max: {}", evaluation.total, evaluation.average, evaluation.weighted_average, evaluation.max_turn);
+    } else {
+        println!("Total: {}, Avg: {}, Max: {}", evaluation.total, evaluation.total as f32 / evaluation.plays.len() as f32, evaluation.max_turn);
+    }
+}
+
+// Reports the wrappers' progress on stderr, stdout is left to the results.
+#[derive(Debug)]
+struct Progress;
+
+impl Observer for Progress {
+    fn notify(&self, event: &Event) {
+        match event {
+            Event::Resumed { tasks } => eprintln!("Resumed {} Tasks.", tasks),
+            // Every tenth of the tasks.
+            Event::TaskDone { done, total } if done * 10 / total != (done - 1) * 10 / total => eprintln!("{} of {} Tasks.", done, total),
+            Event::TaskDone { .. } => {},
+            Event::StageDone { name, stats } => eprintln!("Stage {} Finished: {}", name, stats)
+        }
+    }
+}
+
+// The search from the start word. "parallel" splits it into parallel_wrapper's
+// tasks, which "--checkpoint PATH" records as they finish and resumes from,
+// "baseline" runs baseline_wrapper and anything else start_word_wrapper.
+fn solve_from_start_word(command: &[&str], start_word: WordId, answers: &AnswerSet, words: &BTreeSet<WordId>, table: &PatternTable, config: &SearchConfig) -> io::Result<(Best, SearchStats)> {
+    match command {
+        ["parallel"] => parallel_wrapper(start_word, answers, words, table, config),
+        _ if config.checkpoint_path.is_some() => Err(io::Error::new(io::ErrorKind::InvalidInput, "--checkpoint only applies to the parallel command")),
+        ["baseline"] => baseline_wrapper(start_word, answers, words, table, config),
+        _ => start_word_wrapper(start_word, answers, words, table, config)
+    }
+}
 
 fn main() {
-    let answers: BTreeSet<_> = include_str!("../data/answers.txt").lines().collect();
-    let words: BTreeSet<_> = include_str!("../data/words.txt").lines().collect();
+    let (mut config, mut rest) = SearchConfig::from_args(std::env::args().skip(1)).unwrap();
+    config.observer = Arc::new(Progress);
+
+    // Priors need the dictionary, so they are read here rather than in from_args.
+    // "--priors PATH" weighs the answers by a word<TAB>weight file, "--objective expected"
+    // then minimises the expected guesses. "--likely-answers N" drops answers.txt
+    // for the N heaviest words of the guess list, for clones with no answer list.
+    let weights_file = take_flag(&mut rest, "--priors").map(|path| std::fs::read_to_string(path).unwrap());
+    let weights = weights_file.as_deref().map(|content| parse_weights(content).unwrap());
+    let likely = take_flag(&mut rest, "--likely-answers").map(|limit| parse_likely_answers(&limit).unwrap_or_else(|err| fail(err)));
+    if likely.is_some() && weights.is_none() {
+        fail("--likely-answers ranks the words by --priors, which is missing");
+    }
+    let rest: Vec<&str> = rest.iter().map(String::as_str).collect();
+
+    let table = match (&weights, likely) {
+        (Some(weights), Some(limit)) => {
+            let words = load_word_list(include_str!("../data/words.txt")).unwrap();
+            PatternTable::new(Dictionary::new(&likely_answers(weights, &words, limit), &words))
+        },
+        _ => {
+            let dictionary = Dictionary::from_lists(
+                include_str!("../data/answers.txt"),
+                include_str!("../data/words.txt")
+            ).unwrap();
+            PatternTable::load_or_build("data/patterns.bin", dictionary)
+        }
+    };
+    let dictionary = table.dictionary();
+    config.priors = weights.map(|weights| Arc::new(Priors::from_map(&weights, dictionary).unwrap()));
+
+    let answers = dictionary.answers();
+    let words = dictionary.guesses();
+    let start_word = dictionary.id("salet").unwrap();
 
     // 1000, total 3301, max 6
     // 1075, total 3587, max 6
@@ -21,30 +168,228 @@ fn main() {
     // 1300, total 4412, max 6, 18.97s
     // 1400, total 4793, max 6, 37.64s
     // all, total 8116, max 7, 14.26s with hack.
-    let best = start_word_wrapper("salet", &answers, &words);
+    let searcher = Searcher::new(&table, config.clone());
+    let best = match rest.as_slice() {
+        // Splitting parallel_wrapper over processes: write the tasks, solve shards, merge them.
+        ["manifest", path] => {
+            let manifest = Manifest::new(start_word, &answers, &words, &table, &config);
+            manifest.save_to_path(path, dictionary).unwrap();
+            println!("{} tasks written to {}", manifest.tasks.len(), path);
+            return;
+        },
+        ["shard", index, count, manifest, path] => {
+            let manifest = Manifest::load_from_path(manifest, dictionary).unwrap();
+            let (index, count) = (index.parse().unwrap(), count.parse().unwrap());
+            let done = manifest.solve_shard(index, count, &answers, &words, &table, &config, path).unwrap();
+            println!("{} of {} tasks in shard {} of {} done", done, manifest.shard(index, count).count(), index, count);
+            return;
+        },
+        ["merge", manifest, paths @ ..] => {
+            let manifest = Manifest::load_from_path(manifest, dictionary).unwrap();
+            manifest.merge_shards(paths, &answers, &words, &table, &config).unwrap()
+        },
+        // Ranks the top openers by heuristic score in both modes, "parallel" runs parallel_wrapper per opener.
+        ["leaderboard", top, wrapper, checkpoint @ ..] => {
+            let sweep = Sweep {
+                top: top.parse().unwrap(),
+                parallel: *wrapper == "parallel",
+                checkpoint_path: checkpoint.first().map(PathBuf::from)
+            };
+            print!("{}", sweep.run(&answers, &words, &table, &config).unwrap_or_else(|err| fail(err)).to_tsv(dictionary));
+            return;
+        },
+        // Every heuristic from the start word with the rest of the configured search.
+        ["heuristics"] => {
+            println!("heuristic\ttotal\taverage\tworst\tnodes\tseconds");
+            for report in compare_heuristics(&all_heuristics(), Some(start_word), &answers, &words, &table, &config) {
+                let best = &report.best;
+                println!("{}\t{}\t{:.4}\t{}\t{}\t{:.2}", report.name, best.total_count, best.total_count as f64 / answers.len() as f64, best.max_level, report.nodes, report.elapsed.as_secs_f64());
+            }
+            return;
+        },
+        // Greedy guess after moves like "salet:00120", an optional leading number is the lookahead width.
+        ["suggest", moves @ ..] => {
+            let (lookahead, moves) = match moves.split_first() {
+                Some((width, moves)) if width.parse::<usize>().is_ok() => (width.parse().ok(), moves),
+                _ => (None, moves)
+            };
+            let greedy = Greedy::new(&table, config.clone(), lookahead);
+
+            let (mut remaining, mut allowed) = (answers.clone(), words.clone());
+            for played in moves {
+                let (guess, pattern) = parse_move(dictionary, played);
+                remaining = filter_available_answers(&table, guess, pattern, &remaining);
+                allowed = searcher.narrow(guess, pattern, &allowed);
+            }
+
+            match greedy.suggest(&remaining, &allowed) {
+                Some(guess) => println!("{} answers left, {} suggests {}", remaining.len(), greedy.name(), dictionary.word(guess)),
+                None => println!("{} answers left, no guess tells them apart", remaining.len())
+            }
+            return;
+        },
+        // Replays a saved tree such as data/wordle.json over every answer, nothing is solved.
+        // A tree it cannot play through is reported instead.
+        ["evaluate", path] => {
+            let tree = DecisionTree::load_from_path(path, dictionary).unwrap();
+            let evaluator = Evaluator {
+                answers: &answers,
+                words: &words,
+                table: &table,
+                priors: config.priors.as_deref()
+            };
+            match evaluator.evaluate(tree, config.hard_mode) {
+                Ok(evaluation) => print_evaluation(dictionary, &evaluation, config.priors.is_some()),
+                Err(report) => println!("{}", report.to_json(dictionary).unwrap())
+            }
+            return;
+        },
+        // Rewrites a saved tree, compact binary when the output ends in .bin and JSON otherwise.
+        ["convert", from, to] => {
+            let tree = DecisionTree::load_from_path(from, dictionary).unwrap();
+            if to.ends_with(".bin") {
+                tree.save_binary_to_path(to, dictionary).unwrap();
+            } else {
+                tree.save_to_path(to, dictionary).unwrap();
+            }
+            println!("{} nodes written to {}", tree.node_count(), to);
+            return;
+        },
+        // A saved tree as Graphviz or Mermaid source, "--depth N" levels of edges below its root, "--squares"
+        // for coloured pattern labels and moves like "salet:00120" to draw only what follows them.
+        [format @ ("dot" | "mermaid"), path, args @ ..] => {
+            let tree = DecisionTree::load_from_path(path, dictionary).unwrap();
+            let mut options = ExportOptions::default();
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                match *arg {
+                    "--depth" => options.max_depth = Some(args.next().expect("missing value for --depth").parse().unwrap()),
+                    "--squares" => options.squares = true,
+                    played => options.path.push(parse_move(dictionary, played))
+                }
+            }
+
+            let source = if *format == "dot" { to_dot(&tree, &answers, &table, &options) } else { to_mermaid(&tree, &answers, &table, &options) };
+            match source {
+                Ok(source) => print!("{}", source),
+                Err(err) => println!("{}", err)
+            }
+            return;
+        },
+        // A saved tree as a cheat sheet to follow by hand, "--markdown" for Markdown, "--color"
+        // for terminals, "--rare N" folds branches with fewer than N answers left.
+        ["sheet", path, args @ ..] => {
+            let tree = DecisionTree::load_from_path(path, dictionary).unwrap();
+            let mut options = SheetOptions::default();
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                match *arg {
+                    "--markdown" => options.markdown = true,
+                    "--color" => options.color = true,
+                    "--rare" => options.rare = args.next().expect("missing value for --rare").parse().unwrap(),
+                    "--list" => options.list_limit = Some(args.next().expect("missing value for --list").parse().unwrap()),
+                    other => panic!("unknown sheet option {:?}", other)
+                }
+            }
+            print!("{}", cheat_sheet(&tree, &answers, &table, &options));
+            return;
+        },
+        // Every problem of a saved tree as JSON, answers may take up to TURNS guesses (6).
+        ["validate", path, turns @ ..] => {
+            let tree = DecisionTree::load_from_path(path, dictionary).unwrap();
+            let turn_cap = turns.first().map_or(6, |turns| turns.parse().unwrap());
+            println!("{}", validate(&tree, &answers, &words, &table, config.hard_mode, turn_cap).to_json(dictionary).unwrap());
+            return;
+        },
+        // The saved tree's next guess after moves like "salet:00120".
+        ["replay", path, moves @ ..] => {
+            let tree = DecisionTree::load_from_path(path, dictionary).unwrap();
+            let mut node = &tree;
+            for played in moves {
+                let (guess, pattern) = parse_move(dictionary, played);
+                if node.guess != Some(guess) {
+                    println!("The tree does not play {} here", dictionary.word(guess));
+                    return;
+                }
+                let Some(next) = node.branch.get(&pattern) else {
+                    println!("No answer shows {} after {}", Checker::format_pattern(pattern, dictionary.word_len()), dictionary.word(guess));
+                    return;
+                };
+                node = next;
+            }
+
+            match node.guess {
+                Some(guess) => println!("The tree plays {}", dictionary.word(guess)),
+                None => println!("Solved")
+            }
+            return;
+        },
+        // A greedy tree as a baseline for the search, an optional number is the lookahead width.
+        ["greedy", lookahead @ ..] => Greedy::new(&table, config.clone(), lookahead.first().map(|width| width.parse().unwrap())).tree(&answers, &words),
+        [command @ ("parallel" | "baseline")] if config.exact => fail(format!("--exact searches from the root, it does not run with {}", command)),
+        _ if config.exact => searcher.search(0, &answers, &words),
+        _ => {
+            let (best, stats) = solve_from_start_word(&rest, start_word, &answers, &words, &table, &config).unwrap_or_else(|err| fail(err));
+            eprintln!("{}", stats);
+            best
+        }
+    };
 
     println!("{}, {}", best.max_level, best.total_count);
+
+    let certificate = searcher.certify(&best, &answers, &words);
+    println!("Proven optimal: {}, lower bound: {}, gap: {}", certificate.proven_optimal, config.objective.score(&certificate.lower_bound), certificate.gap);
     
-    best.decision_tree.to_json().unwrap();
+    println!("{}", best.decision_tree.to_json(dictionary));
 
     let evaluator = Evaluator {
         answers: &answers,
-        words: &words
+        words: &words,
+        table: &table,
+        priors: config.priors.as_deref()
     };
 
-    evaluator.evaluate(best.decision_tree, true);
+    let evaluation = evaluator.evaluate(best.decision_tree, config.hard_mode).expect("searched trees are playable");
+    print_evaluation(dictionary, &evaluation, config.priors.is_some());
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::{Arc, Mutex};
     use std::collections::{BTreeMap, BTreeSet};
+    use std::sync::Arc;
+    use std::sync::atomic::Ordering::Relaxed;
+    use std::time::Duration;
 
     use crate::utils::*;
     use crate::game::{Checker, Evaluator};
-    use crate::common::{Restriction, Best, Cache, DecisionTree, Counter};
+    use crate::common::{Restriction, Best, DecisionTree, WordListError, DEPTH_SLOTS, Task};
     use crate::wrapper::{start_word_wrapper, parallel_wrapper, baseline_wrapper};
-    use crate::dfs::{dfs, dfs_with_cache};    
+    use crate::dfs::Searcher;
+    use crate::config::{SearchConfig, Beam, Budget};
+    use crate::objective::*;
+    use crate::observer::*;
+    use crate::transposition::{Lookup, StateKey, TranspositionTable};
+    use crate::encoding::*;
+    use crate::checkpoint::Checkpoint;
+    use crate::shard::Manifest;
+    use crate::leaderboard::{Sweep, opener_scores};
+    use crate::heuristic::*;
+    use crate::strategy::{Greedy, Strategy};
+    use crate::prior::*;
+    use crate::validate::{validate, Problem, Issue};
+    use crate::export::{ExportOptions, pattern_label, to_dot, to_mermaid};
+    use crate::sheet::{SheetOptions, cheat_sheet};
+    use crate::utils::stat_color;
+    use crate::table::PatternTable;
+    use crate::answer_set::AnswerSet;
+    use crate::dictionary::{Dictionary, WordId, UnknownWord};
+
+    fn setup(answers: &BTreeSet<&str>, words: &BTreeSet<&str>) -> (PatternTable, AnswerSet, BTreeSet<WordId>) {
+        let table = PatternTable::new(Dictionary::new(answers, words));
+        let answers = table.dictionary().answers();
+        let words = table.dictionary().ids(words.iter().cloned()).unwrap();
+        (table, answers, words)
+    }
 
 
     #[test]
@@ -61,15 +406,38 @@ mod tests {
 
     #[test]
     fn test_if_success() {
-        assert_eq!(Checker::is_success_pattern(9), false);
-        assert_eq!(Checker::is_success_pattern(242), true);
+        assert!(!Checker::is_success_pattern(9, 5));
+        assert!(Checker::is_success_pattern(242, 5));
+        assert!(Checker::is_success_pattern(80, 4));
+        assert!(Checker::is_success_pattern(6560, 8));
+        assert!(!Checker::is_success_pattern(242, 6));
+    }
+
+    #[test]
+    fn test_check_other_lengths() {
+        assert_eq!(Checker::check("abc", "abc"), 26);
+        assert_eq!(Checker::check("abcd", "dcba"), 40);
+        assert_eq!(Checker::check("abcdef", "abcdeg"), 242);
+        assert_eq!(Checker::check("abcdefgh", "abcdefgh"), 6560);
+    }
+
+    #[test]
+    fn test_load_word_list() {
+        assert_eq!(load_word_list("abcd\nefgh\n\n"), Ok(BTreeSet::from(["abcd", "efgh"])));
+        assert_eq!(load_word_list(""), Err(WordListError::Empty));
+        assert_eq!(load_word_list("ab\n"), Err(WordListError::UnsupportedLength { word: "ab".to_string(), length: 2 }));
+        assert_eq!(load_word_list("abcd\nabcde"), Err(WordListError::MixedLengths { expected: 4, word: "abcde".to_string() }));
+        assert_eq!(load_word_lists("abcd", "abcde").map(|_| ()), Err(WordListError::MixedLengths { expected: 4, word: "abcde".to_string() }));
     }
 
     #[test]
     fn test_group_by_pattern() {
-        assert_eq!(group_by_pattern("salet", &BTreeSet::from(["sblet", "sclet", "zzzzz"])), BTreeMap::from([
-           (236, BTreeSet::from(["sblet", "sclet"])), // GBGGG
-           (0, BTreeSet::from(["zzzzz"])) 
+        let (table, answers, _) = setup(&BTreeSet::from(["sblet", "sclet", "zzzzz"]), &BTreeSet::from(["salet"]));
+        let dictionary = table.dictionary();
+
+        assert_eq!(group_by_pattern(&table, dictionary.id("salet").unwrap(), &answers), BTreeMap::from([
+           (236, dictionary.ids(["sblet", "sclet"]).unwrap().into_iter().collect()), // GBGGG
+           (0, dictionary.ids(["zzzzz"]).unwrap().into_iter().collect())
         ]));
     }
 
@@ -92,10 +460,10 @@ mod tests {
             required_yellow: BTreeMap::from([('c', 2)]),
         });
 
-        assert_eq!(restriction_a.evaluate("aazcc"), true);
-        assert_eq!(restriction_a.evaluate("aaccz"), true);
-        assert_eq!(restriction_a.evaluate("azbcc"), false);
-        assert_eq!(restriction_a.evaluate("aabbc"), false);
+        assert!(restriction_a.evaluate("aazcc"));
+        assert!(restriction_a.evaluate("aaccz"));
+        assert!(!restriction_a.evaluate("azbcc"));
+        assert!(!restriction_a.evaluate("aabbc"));
     }
 
     #[test]
@@ -106,40 +474,51 @@ mod tests {
         };
 
         let words = BTreeSet::from(["aazcc", "aaccz", "azbcc", "aabbc"]);
+        let dictionary = Dictionary::new(&words, &words);
 
-        assert_eq!(filter_available_guesses(&restriction, &words), BTreeSet::from(["aazcc", "aaccz"]));
+        assert_eq!(
+            filter_available_guesses(&dictionary, &restriction, &dictionary.guesses()),
+            dictionary.ids(["aazcc", "aaccz"]).unwrap()
+        );
     }
 
     #[test]
     fn test_decision_tree() {
-        let a = DecisionTree::from("fiveb", BTreeMap::from([]));
-        let b = DecisionTree::from("salte", BTreeMap::from([]));
+        let a = DecisionTree::from(0, BTreeMap::from([]));
+        let b = DecisionTree::from(1, BTreeMap::from([]));
 
-        let mut c = &DecisionTree::from("salet", BTreeMap::from([
+        let mut c = &DecisionTree::from(2, BTreeMap::from([
             (0, a),
             (134, b),
         ]));
         
-        assert_eq!(c.guess(), "salet");
-        c = DecisionTree::next(&c, 134);
-        assert_eq!(c.guess(), "salte");
+        assert_eq!(c.guess(), Some(2));
+        c = DecisionTree::next(c, 134);
+        assert_eq!(c.guess(), Some(1));
+        assert_eq!(DecisionTree::new().guess(), None);
         
     }
 
     #[test]
     fn test_single_search() {
-        let mut counter = Counter {
-            result_counter: 0,
-            no_result_counter: 0,
-            baseline_counter: 0
-        };
+        let (table, answers, words) = setup(&BTreeSet::from(["salet"]), &BTreeSet::from(["salet"]));
 
-        let best = dfs_with_cache(0, &BTreeSet::from(["salet"]), &BTreeSet::from(["salet"]), Restriction::new(), &Arc::new(Mutex::new(Cache::new())), false, &mut counter);
+        let searcher = Searcher::new(&table, SearchConfig {
+            use_cache: true,
+            beam: Beam::Unlimited,
+            ..SearchConfig::default()
+        });
+
+        let best = searcher.search(0, &answers, &words);
+        let mut depth_counts = [0; DEPTH_SLOTS];
+        depth_counts[1] = 1;
         assert_eq!(best, Best {
             has_result: true,
             max_level: 1,
             total_count: 1,
-            decision_tree: DecisionTree::from("salet", BTreeMap::from([
+            depth_counts,
+            weighted_count: 1.0,
+            decision_tree: DecisionTree::from(0, BTreeMap::from([
                 (242, DecisionTree::new())
             ]))
         })
@@ -170,23 +549,27 @@ mod tests {
         "abort",
         "salet"]);
 
-        let mut counter = Counter {
-            result_counter: 0,
-            no_result_counter: 0,
-            baseline_counter: 0
-        };
+        let (table, answers, words) = setup(&answers, &words);
 
-        let best = dfs_with_cache(0, &answers, &words, Restriction::new(), &Arc::new(Mutex::new(Cache::new())), false, &mut counter);
-        assert_eq!(best.has_result, true);
+        let searcher = Searcher::new(&table, SearchConfig {
+            use_cache: true,
+            beam: Beam::Unlimited,
+            ..SearchConfig::default()
+        });
+
+        let best = searcher.search(0, &answers, &words);
+        assert!(best.has_result);
         assert_eq!(best.max_level, 3);
         assert_eq!(best.total_count, 21); 
 
         let evaluator = Evaluator {
             answers: &answers,
-            words: &words
+            words: &words,
+            table: &table,
+            priors: None
         };
 
-        evaluator.evaluate(best.decision_tree, true);
+        evaluator.evaluate(best.decision_tree, true).unwrap();
     }
 
     #[test]
@@ -214,34 +597,45 @@ mod tests {
         "abort",
         "salet"]);
 
-        let best = dfs(0, &answers, &words);
-        assert_eq!(best.has_result, true);
+        let (table, answers, words) = setup(&answers, &words);
+
+        let best = Searcher::new(&table, SearchConfig::default()).search(0, &answers, &words);
+        assert!(best.has_result);
         assert_eq!(best.max_level, 3);
         assert_eq!(best.total_count, 21); 
 
         let evaluator = Evaluator {
             answers: &answers,
-            words: &words
+            words: &words,
+            table: &table,
+            priors: None
         };
 
-        evaluator.evaluate(best.decision_tree, true);
+        evaluator.evaluate(best.decision_tree, true).unwrap();
     }
 
     #[test]
     fn test_lower_bound_level() {
-        assert_eq!(get_lower_bound_level(1), 1);
-        assert_eq!(get_lower_bound_level(2), 2);
-        assert_eq!(get_lower_bound_level(5), 2);
-        assert_eq!(get_lower_bound_level(243), 2);
-        assert_eq!(get_lower_bound_level(244), 3);
+        assert_eq!(get_lower_bound_level(1, 5), 1);
+        assert_eq!(get_lower_bound_level(2, 5), 2);
+        assert_eq!(get_lower_bound_level(5, 5), 2);
+        assert_eq!(get_lower_bound_level(243, 5), 2);
+        assert_eq!(get_lower_bound_level(244, 5), 3);
+        assert_eq!(get_lower_bound_level(82, 4), 3);
+
+        let bound = Best::bound(2, 3, 3.0);
+        assert_eq!((bound.total_count, bound.depth_counts[1], bound.depth_counts[2]), (5, 1, 2));
+        assert_eq!(Best::bound(2, 0, 0.0), Best::empty());
     }
 
     #[test]
     fn test_get_entropy() {
-        assert_eq!(get_entropy(242, 1), 0);
-        assert_eq!(get_entropy(0, 2), 3);
-        assert_eq!(get_entropy(0, 5), 9);
-        assert_eq!(get_entropy(0, 244), 487);
+        assert_eq!(get_entropy(242, 5, 1), 0);
+        assert_eq!(get_entropy(0, 5, 2), 3);
+        assert_eq!(get_entropy(0, 5, 5), 9);
+        assert_eq!(get_entropy(0, 5, 244), 487);
+        assert_eq!(get_entropy(242, 6, 2), 3);
+        assert_eq!(get_entropy(728, 6, 1), 0);
     }
 
     #[test]
@@ -269,17 +663,21 @@ mod tests {
         "abort",
         "salet"]);
 
-        let best = start_word_wrapper("salet", &answers, &words);
-        assert_eq!(best.has_result, true);
+        let (table, answers, words) = setup(&answers, &words);
+
+        let best = start_word_wrapper(table.dictionary().id("salet").unwrap(), &answers, &words, &table, &SearchConfig::default()).unwrap().0;
+        assert!(best.has_result);
         assert_eq!(best.max_level, 3);
         assert_eq!(best.total_count, 23); 
 
         let evaluator = Evaluator {
             answers: &answers,
-            words: &words
+            words: &words,
+            table: &table,
+            priors: None
         };
 
-        evaluator.evaluate(best.decision_tree, true);
+        evaluator.evaluate(best.decision_tree, true).unwrap();
     }
 
     #[test]
@@ -307,17 +705,21 @@ mod tests {
         "abort",
         "salet"]);
 
-        let best = parallel_wrapper("salet", &answers, &words);
-        assert_eq!(best.has_result, true);
+        let (table, answers, words) = setup(&answers, &words);
+
+        let best = parallel_wrapper(table.dictionary().id("salet").unwrap(), &answers, &words, &table, &SearchConfig::default()).unwrap().0;
+        assert!(best.has_result);
         assert_eq!(best.max_level, 3);
         assert_eq!(best.total_count, 23); 
 
         let evaluator = Evaluator {
             answers: &answers,
-            words: &words
+            words: &words,
+            table: &table,
+            priors: None
         };
 
-        evaluator.evaluate(best.decision_tree, true);
+        evaluator.evaluate(best.decision_tree, true).unwrap();
     }
 
     #[test]
@@ -345,16 +747,1249 @@ mod tests {
         "abort",
         "salet"]);
 
-        let best = baseline_wrapper("salet", &answers, &words);
-        assert_eq!(best.has_result, true);
+        let (table, answers, words) = setup(&answers, &words);
+
+        let best = baseline_wrapper(table.dictionary().id("salet").unwrap(), &answers, &words, &table, &SearchConfig::default()).unwrap().0;
+        assert!(best.has_result);
         assert_eq!(best.max_level, 3);
         assert_eq!(best.total_count, 23); 
 
         let evaluator = Evaluator {
             answers: &answers,
-            words: &words
+            words: &words,
+            table: &table,
+            priors: None
+        };
+
+        evaluator.evaluate(best.decision_tree, true).unwrap();
+    }
+
+    #[test]
+    fn test_a_few_search_other_lengths() {
+        let answers = BTreeSet::from(["bake", "cake", "fake", "lake", "make", "rake", "take", "wake"]);
+        let words = BTreeSet::from(["bake", "cake", "fake", "lake", "make", "rake", "take", "wake", "crwm", "flbt"]);
+        let (table, answers, words) = setup(&answers, &words);
+
+        let best = Searcher::new(&table, SearchConfig::default()).search(0, &answers, &words);
+        assert!(best.has_result);
+
+        let evaluator = Evaluator {
+            answers: &answers,
+            words: &words,
+            table: &table,
+            priors: None
+        };
+
+        evaluator.evaluate(best.decision_tree, false).unwrap();
+
+        let answers = BTreeSet::from(["banana", "bandit", "candle", "handle", "random"]);
+        let (table, answers, words) = setup(&answers, &answers);
+        let best = start_word_wrapper(table.dictionary().id("handle").unwrap(), &answers, &words, &table, &SearchConfig::default()).unwrap().0;
+        assert!(best.has_result);
+        assert_eq!(best.total_count, 10);
+
+        let evaluator = Evaluator {
+            answers: &answers,
+            words: &words,
+            table: &table,
+            priors: None
+        };
+
+        evaluator.evaluate(best.decision_tree, true).unwrap();
+    }
+
+    #[test]
+    fn test_pattern_table() {
+        let answers = BTreeSet::from(["aback", "abase", "error", "crash"]);
+        let words = BTreeSet::from(["admin", "aaaar", "salet"]);
+        let (table, answers, _) = setup(&answers, &words);
+        let dictionary = table.dictionary();
+
+        for guess in dictionary.guesses() {
+            for answer in answers.iter() {
+                assert_eq!(table.get(guess, answer), Checker::check(dictionary.word(answer), dictionary.word(guess)));
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("wordle-rs-table-{}.bin", std::process::id()));
+        table.save_to_path(&path).unwrap();
+
+        let loaded = PatternTable::load_from_path(&path, dictionary.clone()).unwrap();
+        for guess in dictionary.guesses() {
+            assert_eq!(loaded.row(guess), table.row(guess));
+        }
+
+        let other = Dictionary::new(&BTreeSet::from(["aback", "abase", "error", "crash"]), &BTreeSet::from(["admin", "aaaar", "salty"]));
+        assert!(PatternTable::load_from_path(&path, other).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_stable_hashes() {
+        // Plain FNV-1a over bytes, the checksum of every binary file header.
+        assert_eq!(fnv1a(FNV_OFFSET, b'a' as u64), 0xaf63dc4c8601ec8c);
+
+        let mut a = StableHasher::new(0);
+        let mut b = StableHasher::new(1);
+        a.add_bytes(b"salet");
+        b.add_bytes(b"salet");
+        assert_ne!(a.0, b.0);
+
+        let mut bytes: &[u8] = &[7, 0x34, 0x12, 1, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!((read_u8(&mut bytes).unwrap(), read_u16(&mut bytes).unwrap(), read_u32(&mut bytes).unwrap(), read_u64(&mut bytes).unwrap()), (7, 0x1234, 1, 2));
+        assert_eq!(read_u8(&mut bytes).unwrap_err().kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_dictionary() {
+        let dictionary = Dictionary::new(&BTreeSet::from(["error", "crash"]), &BTreeSet::from(["admin", "crash", "salet"]));
+
+        assert_eq!(dictionary.len(), 4);
+        assert_eq!(dictionary.answer_count(), 2);
+        assert_eq!(dictionary.iter().collect::<Vec<_>>(), vec!["crash", "error", "admin", "salet"]);
+        assert_eq!(dictionary.id("error"), Ok(1));
+        assert!(dictionary.is_answer(1));
+        assert!(!dictionary.is_answer(dictionary.id("salet").unwrap()));
+        assert_eq!(dictionary.words(&dictionary.ids(["error", "crash"]).unwrap()), vec!["crash", "error"]);
+        assert_eq!(dictionary.id("zzzzz"), Err(UnknownWord("zzzzz".to_string())));
+    }
+
+    #[test]
+    fn test_answer_set() {
+        let mut set = AnswerSet::full(130);
+        assert_eq!(set.len(), 130);
+        assert!(set.contains(0) && set.contains(63) && set.contains(64) && set.contains(129));
+        assert!(!set.contains(130));
+
+        set.remove(64);
+        assert_eq!(set.len(), 129);
+        assert_eq!(set.iter().take(3).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(AnswerSet::new().first(), None);
+        assert!(AnswerSet::new().is_empty());
+
+        let row: Vec<_> = (0..130).map(|id| (id % 3) as u16).collect();
+        let groups = set.partition(&row);
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups.values().map(|g| g.len()).sum::<usize>(), 129);
+        assert_eq!(groups[&1], set.filter(&row, 1));
+        assert!(!groups[&1].contains(64));
+        assert_eq!(AnswerSet::full(AnswerSet::CAPACITY).len(), AnswerSet::CAPACITY);
+    }
+
+    #[test]
+    fn test_rank_guesses() {
+        let answers = BTreeSet::from(["aback", "abase", "abate", "abbey", "abbot", "abhor", "abide", "abled", "abode", "abort"]);
+        let words = BTreeSet::from(["salet", "abbot", "zzzzz", "qqqqq", "crane"]);
+        let (table, answers, _) = setup(&answers, &words);
+        let guesses = table.dictionary().guesses();
+
+        let mut expected: Vec<_> = Vec::new();
+        for guess in guesses.iter() {
+            let ranked = get_entropy_sum(&table, *guess, &answers);
+            if ranked.2.len() == 1 {
+                continue;
+            }
+            if !expected.iter().any(|(_, _, groups)| *groups == ranked.2) {
+                expected.push(ranked);
+            }
+        }
+        expected.sort_by_key(|(_, entropy, _)| *entropy);
+        let expected: Vec<_> = expected.into_iter().map(|(guess, entropy, groups)| (guess, entropy as f64, groups)).collect();
+
+        assert_eq!(rank_guesses(&table, &answers, &guesses, usize::MAX, true, &CurrentScore, None), expected);
+        assert_eq!(rank_guesses(&table, &answers, &guesses, 3, true, &CurrentScore, None), expected[..3].to_vec());
+
+        let mut counter = PatternCounter::new(5);
+        let answer_ids: Vec<_> = answers.iter().collect();
+        let zzzzz = table.dictionary().id("zzzzz").unwrap();
+        let qqqqq = table.dictionary().id("qqqqq").unwrap();
+        let fingerprint = counter.count(table.row(zzzzz), &answer_ids, None);
+        assert_eq!(counter.iter().collect::<Vec<_>>(), vec![(0, 10)]);
+        assert_eq!(counter.count(table.row(qqqqq), &answer_ids, None), fingerprint);
+        assert!(counter.splits_nothing(5));
+        assert!(!rank_guesses(&table, &answers, &guesses, usize::MAX, true, &CurrentScore, None).iter().any(|(guess, _, _)| *guess == qqqqq));
+    }
+
+    #[test]
+    fn test_search_config() {
+        assert_eq!("13".parse(), Ok(Beam::Fixed(13)));
+        assert_eq!("15,13,8".parse(), Ok(Beam::PerDepth(vec![15, 13, 8])));
+        assert_eq!("unlimited".parse(), Ok(Beam::Unlimited));
+        assert!("wide".parse::<Beam>().is_err());
+
+        let beam = Beam::PerDepth(vec![15, 13, 8]);
+        assert_eq!(beam.width(0), 15);
+        assert_eq!(beam.width(2), 8);
+        assert_eq!(beam.width(5), 8);
+        assert_eq!(Beam::Unlimited.width(0), usize::MAX);
+
+        let args = ["--beam", "20,10", "--max-turns", "5", "--normal", "--cache", "--parallel", "100", "extra"].map(String::from);
+        let (config, rest) = SearchConfig::from_args(args).unwrap();
+        assert_eq!(config, SearchConfig {
+            max_turns: 5,
+            beam: Beam::PerDepth(vec![20, 10]),
+            heuristic: Arc::new(CurrentScore),
+            use_cache: true,
+            cache_bytes: None,
+            cache_path: None,
+            checkpoint_path: None,
+            hard_mode: false,
+            small_set_threshold: 3,
+            objective: Arc::new(TotalGuesses),
+            priors: None,
+            exact: false,
+            budget: Budget::default(),
+            parallel_threshold: 100,
+            observer: Arc::new(Silent)
+        });
+        assert_eq!(rest, vec!["extra".to_string()]);
+        assert!(SearchConfig::from_args(["--beam".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_search_turn_cap() {
+        let answers = BTreeSet::from(["aback", "abase", "abate", "abbey", "abbot", "abhor", "abide", "abled", "abode", "abort"]);
+        let (table, answers, words) = setup(&answers, &answers);
+
+        let best = Searcher::new(&table, SearchConfig::default()).search(0, &answers, &words);
+        assert!(best.has_result);
+
+        let capped = Searcher::new(&table, SearchConfig { max_turns: 1, ..SearchConfig::default() })
+            .search(0, &answers, &words);
+        assert!(!capped.has_result);
+
+        let capped = Searcher::new(&table, SearchConfig { max_turns: best.max_level, ..SearchConfig::default() })
+            .search(0, &answers, &words);
+        assert!(capped.has_result);
+    }
+
+    #[test]
+    fn test_normal_mode_search() {
+        let answers = BTreeSet::from(["bake", "cake", "fake", "lake", "make", "rake", "take", "wake"]);
+        let words = BTreeSet::from(["bake", "cake", "fake", "lake", "make", "rake", "take", "wake", "bclf", "mrtw"]);
+        let (table, answers, words) = setup(&answers, &words);
+
+        let hard = SearchConfig { max_turns: 10, beam: Beam::Unlimited, ..SearchConfig::default() };
+        let normal = SearchConfig { hard_mode: false, ..hard.clone() };
+
+        let start_word = table.dictionary().id("bake").unwrap();
+        let hard_best = start_word_wrapper(start_word, &answers, &words, &table, &hard).unwrap().0;
+        let normal_best = start_word_wrapper(start_word, &answers, &words, &table, &normal).unwrap().0;
+        assert!(hard_best.has_result && normal_best.has_result);
+        assert!(normal_best.total_count < hard_best.total_count);
+
+        let evaluator = Evaluator {
+            answers: &answers,
+            words: &words,
+            table: &table,
+            priors: None
+        };
+
+        for best in [
+            normal_best,
+            parallel_wrapper(start_word, &answers, &words, &table, &normal).unwrap().0,
+            baseline_wrapper(start_word, &answers, &words, &table, &normal).unwrap().0,
+        ] {
+            assert!(best.has_result);
+            evaluator.evaluate(best.decision_tree, false).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_objectives() {
+        let answers = BTreeSet::from(["award", "beard", "board", "chard", "chord", "fjord", "gourd", "guard", "heard", "hoard", "shard", "sword", "third", "weird"]);
+        let (table, answers, words) = setup(&answers, &answers);
+
+        let search = |objective: &str| {
+            let config = SearchConfig {
+                max_turns: 10,
+                beam: Beam::Unlimited,
+                use_cache: true,
+                objective: Arc::from(parse_objective(objective).unwrap()),
+                ..SearchConfig::default()
+            };
+            Searcher::new(&table, config).search(0, &answers, &words)
+        };
+
+        let total = search("total");
+        assert_eq!((total.max_level, total.total_count), (4, 32));
+        assert_eq!(total.depth_counts[4], 1);
+
+        let worst = search("worst");
+        assert_eq!((worst.max_level, worst.total_count), (3, 33));
+
+        let above = search("above:3");
+        assert_eq!(above.depth_counts[4..].iter().sum::<u32>(), 0);
+        assert_eq!(above.total_count, 33);
+
+        // A fourth guess costs more than the extra answers found with three.
+        let cost = search("cost:1,2,3,10");
+        assert_eq!((cost.max_level, cost.total_count), (3, 33));
+
+        // Uniform weights are the plain total.
+        let expected = Searcher::new(&table, SearchConfig {
+            max_turns: 10,
+            beam: Beam::Unlimited,
+            objective: Arc::new(ExpectedGuesses),
+            priors: Some(Arc::new(Priors::uniform(answers.len()))),
+            ..SearchConfig::default()
+        }).search(0, &answers, &words);
+        assert_eq!(expected.total_count, 32);
+        assert_eq!(expected.weighted_count, 32.0);
+
+        assert!(parse_objective("cost:1,3,2").is_err());
+        assert!(parse_objective("fastest").is_err());
+
+        let evaluator = Evaluator {
+            answers: &answers,
+            words: &words,
+            table: &table,
+            priors: None
+        };
+
+        for best in [total, worst, above, cost, expected] {
+            evaluator.evaluate(best.decision_tree, true).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_exact_search() {
+        let answers = BTreeSet::from(["award", "beard", "board", "chard", "chord", "fjord", "gourd", "guard", "heard", "hoard", "shard", "sword", "third", "weird"]);
+        let (table, answers, words) = setup(&answers, &answers);
+
+        let exact = SearchConfig { max_turns: 10, exact: true, ..SearchConfig::default() };
+        let searcher = Searcher::new(&table, exact.clone());
+        let best = searcher.search(0, &answers, &words);
+        let certificate = searcher.certify(&best, &answers, &words);
+        assert!(certificate.proven_optimal);
+        assert_eq!(certificate.gap, 0.0);
+        assert_eq!(best.total_count, 32);
+
+        // A beam of one may miss the optimum, the certificate still bounds it.
+        let searcher = Searcher::new(&table, SearchConfig { max_turns: 10, beam: Beam::Fixed(1), ..SearchConfig::default() });
+        let greedy = searcher.search(0, &answers, &words);
+        let certificate = searcher.certify(&greedy, &answers, &words);
+        assert!(certificate.lower_bound.total_count <= best.total_count);
+        assert_eq!(certificate.gap, (greedy.total_count - certificate.lower_bound.total_count) as f64);
+        assert_eq!(certificate.proven_optimal, certificate.gap == 0.0);
+
+        // Exact hard mode keeps guesses with the same partition, the cache keys on the guesses left.
+        let searcher = Searcher::new(&table, SearchConfig { use_cache: true, ..exact.clone() });
+        let cached = searcher.search(0, &answers, &words);
+        assert_eq!(cached.total_count, 32);
+        assert!(searcher.certify(&cached, &answers, &words).proven_optimal);
+
+        // Too few turns, nothing to prove.
+        let searcher = Searcher::new(&table, SearchConfig { max_turns: 1, ..exact.clone() });
+        let capped = searcher.search(0, &answers, &words);
+        assert!(!searcher.certify(&capped, &answers, &words).proven_optimal);
+
+        let (single_table, single, single_words) = setup(&BTreeSet::from(["salet"]), &BTreeSet::from(["salet"]));
+        let searcher = Searcher::new(&single_table, SearchConfig::default());
+        let single_best = Searcher::new(&single_table, SearchConfig::default()).search(0, &single, &single_words);
+        assert!(searcher.certify(&single_best, &single, &single_words).proven_optimal);
+
+        // An exact config proves nothing it did not search: not a tree from elsewhere,
+        // and not a tree for another root.
+        let greedy = Greedy::new(&table, SearchConfig { max_turns: 10, heuristic: Arc::new(LargestBucket), ..SearchConfig::default() }, None).tree(&answers, &words);
+        assert!(greedy.total_count > best.total_count);
+        let searcher = Searcher::new(&table, exact);
+        assert!(!searcher.certify(&greedy, &answers, &words).proven_optimal);
+        let fewer: AnswerSet = answers.iter().skip(1).collect();
+        searcher.search(0, &fewer, &words);
+        assert!(!searcher.certify(&greedy, &answers, &words).proven_optimal);
+        searcher.search(0, &answers, &words);
+        assert!(!searcher.certify(&greedy, &answers, &words).proven_optimal);
+        assert!(searcher.certify(&best, &answers, &words).proven_optimal);
+
+        let evaluator = Evaluator {
+            answers: &answers,
+            words: &words,
+            table: &table,
+            priors: None
+        };
+
+        evaluator.evaluate(best.decision_tree, true).unwrap();
+    }
+
+    #[test]
+    fn test_anytime_search() {
+        let answers = BTreeSet::from(["award", "beard", "board", "chard", "chord", "fjord", "gourd", "guard", "heard", "hoard", "shard", "sword", "third", "weird"]);
+        let (table, answers, words) = setup(&answers, &answers);
+
+        let full = SearchConfig { max_turns: 10, beam: Beam::Unlimited, ..SearchConfig::default() };
+        let searcher = Searcher::new(&table, full.clone());
+        let best = searcher.search(0, &answers, &words);
+        assert!(!searcher.truncated());
+        let full_nodes = searcher.clock.nodes();
+
+        let evaluator = Evaluator {
+            answers: &answers,
+            words: &words,
+            table: &table,
+            priors: None
+        };
+
+        for budget in [
+            Budget { nodes: Some(0), ..Budget::default() },
+            Budget { nodes: Some(full_nodes / 2), ..Budget::default() },
+            Budget { time: Some(Duration::ZERO), ..Budget::default() },
+        ] {
+            let config = SearchConfig { budget, ..full.clone() };
+            let searcher = Searcher::new(&table, config.clone());
+            let anytime = searcher.search(0, &answers, &words);
+            assert!(anytime.has_result && searcher.truncated());
+            assert!(anytime.total_count >= best.total_count);
+            evaluator.evaluate(anytime.decision_tree, true).unwrap();
+
+            let start_word = table.dictionary().id("board").unwrap();
+            for anytime in [
+                start_word_wrapper(start_word, &answers, &words, &table, &config).unwrap().0,
+                parallel_wrapper(start_word, &answers, &words, &table, &config).unwrap().0,
+            ] {
+                assert!(anytime.has_result);
+                evaluator.evaluate(anytime.decision_tree, true).unwrap();
+            }
+        }
+
+        let args = ["--time-limit", "1.5", "--node-limit", "100"].map(String::from);
+        let (config, _) = SearchConfig::from_args(args).unwrap();
+        assert_eq!(config.budget, Budget { time: Some(Duration::from_millis(1500)), nodes: Some(100) });
+        assert!(SearchConfig::from_args(["--time-limit", "-1"].map(String::from)).is_err());
+    }
+
+    #[test]
+    fn test_transposition_table() {
+        let mut table = TranspositionTable::new(Some(4096));
+        let answers: AnswerSet = [1, 2, 3].into_iter().collect();
+        let guesses = BTreeSet::from([1, 2, 3, 4]);
+        let key = StateKey::new(&answers, &guesses);
+        assert_ne!(key, StateKey::new(&answers, &BTreeSet::from([1, 2, 3])));
+
+        assert_eq!(table.lookup(&key, 3, 0), Lookup::Miss);
+        table.insert(key, 3, Best::single(1, 5), 100, None);
+        assert_eq!(table.lookup(&key, 3, 0), Lookup::Result(Best::single(1, 5), false));
+        assert_eq!(table.lookup(&key, 4, 0), Lookup::Baseline(Best::single(1, 5)));
+        assert_eq!(table.lookup(&key, 2, 0), Lookup::Miss);
+
+        // Failing with two turns left also rules out one.
+        table.insert(key, 2, Best::new(), 10, None);
+        assert_eq!(table.lookup(&key, 2, 0), Lookup::NoResult);
+        assert_eq!(table.lookup(&key, 1, 0), Lookup::NoResult);
+        assert_eq!(table.lookup(&key, 3, 0), Lookup::Result(Best::single(1, 5), false));
+        assert_eq!(table.stats.hit_rate(), 4.0 / 7.0);
+
+        // Filling past the cap drops the cheap entries before the expensive one.
+        for id in 10..100 {
+            let key = StateKey::new(&[id].into_iter().collect(), &guesses);
+            table.insert(key, 2, Best::single(id, 5), 1, None);
+        }
+        assert!(table.bytes() <= 4096);
+        assert!(table.stats.evictions > 0);
+        assert_ne!(table.lookup(&key, 3, 0), Lookup::Miss);
+
+        // What a beam cut guesses from is only final for the same beam, its failures prove nothing.
+        let mut table = TranspositionTable::new(None);
+        let cut = StateKey::new(&[5, 6].into_iter().collect(), &guesses);
+        table.insert(cut, 3, Best::single(5, 5), 1, Some(7));
+        assert_eq!(table.lookup(&cut, 3, 7), Lookup::Result(Best::single(5, 5), true));
+        assert_eq!(table.lookup(&cut, 3, 8), Lookup::Baseline(Best::single(5, 5)));
+        table.insert(cut, 2, Best::new(), 1, Some(7));
+        assert_eq!(table.lookup(&cut, 2, 7), Lookup::Miss);
+
+        // A final result is not replaced by a cut one.
+        table.insert(cut, 3, Best::single(6, 5), 1, None);
+        table.insert(cut, 3, Best::single(5, 5), 1, Some(8));
+        assert_eq!(table.lookup(&cut, 3, 8), Lookup::Result(Best::single(6, 5), false));
+
+        let answers = BTreeSet::from(["aback", "abase", "abate", "abbey", "abbot", "abhor", "abide", "abled", "abode", "abort"]);
+        let words = BTreeSet::from(["aback", "abase", "abate", "abbey", "abbot", "abhor", "abide", "abled", "abode", "abort", "salet"]);
+        let (table, answers, words) = setup(&answers, &words);
+
+        for cache_bytes in [None, Some(2048)] {
+            let searcher = Searcher::new(&table, SearchConfig {
+                use_cache: true,
+                cache_bytes,
+                beam: Beam::Unlimited,
+                ..SearchConfig::default()
+            });
+            let best = searcher.search(0, &answers, &words);
+            assert_eq!(best.total_count, 21);
+            assert!(searcher.cache.stats().misses > 0);
+            assert!(cache_bytes.is_none_or(|cache_bytes| searcher.cache.bytes() <= cache_bytes));
+        }
+    }
+
+    #[test]
+    fn test_cache_no_result() {
+        let answers = BTreeSet::from(["award", "beard", "board", "chard", "chord", "fjord", "gourd", "guard", "heard", "hoard", "shard", "sword", "third", "weird"]);
+        let (table, answers, words) = setup(&answers, &answers);
+
+        let config = SearchConfig { max_turns: 1, beam: Beam::Unlimited, hard_mode: false, use_cache: true, ..SearchConfig::default() };
+        let mut searcher = Searcher::new(&table, config.clone());
+        assert!(!searcher.search(0, &answers, &words).has_result);
+
+        // The failure is remembered for the same number of turns.
+        let nodes = searcher.counter.node_counter.load(Relaxed);
+        assert!(!searcher.search(0, &answers, &words).has_result);
+        assert_eq!(searcher.counter.node_counter.load(Relaxed), nodes + 1);
+        assert_eq!(searcher.counter.no_result_counter.load(Relaxed), 1);
+
+        // But proves nothing about more turns.
+        searcher.config.max_turns = 3;
+        let best = searcher.search(0, &answers, &words);
+        let uncached = Searcher::new(&table, SearchConfig { max_turns: 3, use_cache: false, ..config.clone() }).search(0, &answers, &words);
+        assert!(best.has_result);
+        assert_eq!((best.max_level, best.total_count), (uncached.max_level, uncached.total_count));
+
+        // A result with fewer turns is the baseline for more.
+        searcher.config.max_turns = 4;
+        let more = searcher.search(0, &answers, &words);
+        assert!(searcher.counter.baseline_counter.load(Relaxed) > 0);
+        assert!(more.total_count <= best.total_count);
+        assert_eq!(more, Searcher::new(&table, SearchConfig { max_turns: 4, use_cache: false, ..config.clone() }).search(0, &answers, &words));
+        assert_eq!(searcher.search(0, &answers, &words), more);
+        assert!(searcher.cache.stats().hits > 0);
+
+        // A baseline that ties the search's own tree does not replace it, so what
+        // another thread cached first cannot choose the result.
+        let pair = BTreeSet::from(["award", "beard"]);
+        let (table, answers, words) = setup(&pair, &pair);
+        let searcher = Searcher::new(&table, SearchConfig { max_turns: 2, ..config.clone() });
+        let own = Searcher::new(&table, SearchConfig { max_turns: 2, use_cache: false, ..config.clone() }).search(0, &answers, &words);
+        let (first, second) = (own.decision_tree.guess.unwrap(), words.iter().find(|word| Some(**word) != own.decision_tree.guess).cloned().unwrap());
+        let mut tie = searcher.init(second, &answers);
+        tie.update(table.get(second, second), Best::success());
+        tie.update(table.get(second, first), searcher.single(&[first].into_iter().collect()));
+        tie.finish();
+        assert_eq!((tie.total_count, tie.max_level), (own.total_count, own.max_level));
+        searcher.cache.insert(StateKey::new(&answers, &words), 1, tie, 1, None);
+        assert_eq!(searcher.search(0, &answers, &words), own);
+        assert_eq!(searcher.counter.baseline_counter.load(Relaxed), 1);
+    }
+
+    #[test]
+    fn test_baseline_stages() {
+        let answers: BTreeSet<&str> = include_str!("../data/answers.txt").lines().take(200).collect();
+        let (table, answers, words) = setup(&answers, &answers);
+        let start_word = table.dictionary().id("aback").unwrap();
+        let config = SearchConfig { beam: Beam::Fixed(2), ..SearchConfig::default() };
+
+        // The unlimited stage searches again what the narrow beam cut, starting from its trees.
+        let (narrow, _) = start_word_wrapper(start_word, &answers, &words, &table, &config).unwrap();
+        let (best, stats) = baseline_wrapper(start_word, &answers, &words, &table, &config).unwrap();
+        let (unlimited, _) = start_word_wrapper(start_word, &answers, &words, &table, &SearchConfig { beam: Beam::Unlimited, ..config.clone() }).unwrap();
+        assert!(best.total_count < narrow.total_count);
+        assert_eq!(best.total_count, unlimited.total_count);
+        assert!(stats.cache_misses.iter().sum::<u64>() > stats.cache_hits.iter().sum::<u64>());
+    }
+
+    #[test]
+    fn test_solver_cache_file() {
+        let answers = BTreeSet::from(["award", "beard", "board", "chard", "chord", "fjord", "gourd", "guard", "heard", "hoard", "shard", "sword", "third", "weird"]);
+        let (table, answers, words) = setup(&answers, &answers);
+
+        let path = std::env::temp_dir().join(format!("wordle-rs-cache-{}.bin", std::process::id()));
+        let config = SearchConfig {
+            max_turns: 10,
+            beam: Beam::Unlimited,
+            use_cache: true,
+            cache_path: Some(path.clone()),
+            ..SearchConfig::default()
+        };
+
+        let searcher = Searcher::new(&table, config.clone());
+        let best = searcher.search(0, &answers, &words);
+        searcher.save_cache().unwrap();
+
+        // A later run answers from the file alone.
+        let mut reloaded = Searcher::new(&table, config.clone());
+        reloaded.load_cache();
+        assert_eq!(reloaded.cache.len(), searcher.cache.len());
+        assert_eq!(reloaded.search(0, &answers, &words), best);
+        assert_eq!(reloaded.counter.node_counter.load(Relaxed), 1);
+
+        // Other beams and heuristics share the file, other turn caps, objectives and
+        // small set cutoffs do not.
+        for (other, shared) in [
+            (SearchConfig { beam: Beam::Fixed(3), ..config.clone() }, true),
+            (SearchConfig { heuristic: Arc::new(ShannonEntropy), ..config.clone() }, true),
+            (SearchConfig { small_set_threshold: 1, ..config.clone() }, false),
+            (SearchConfig { max_turns: 5, ..config.clone() }, false),
+            (SearchConfig { objective: Arc::new(WorstCase), ..config.clone() }, false),
+            (SearchConfig { hard_mode: false, ..config.clone() }, false),
+        ] {
+            let mut searcher = Searcher::new(&table, other);
+            searcher.load_cache();
+            assert_eq!(searcher.cache.is_empty(), !shared);
+        }
+
+        // What a narrower beam cut guesses from only seeds a wider run as baselines.
+        let narrow = SearchConfig { beam: Beam::Fixed(1), ..config.clone() };
+        std::fs::remove_file(&path).unwrap();
+        let searcher = Searcher::new(&table, narrow.clone());
+        let narrow_best = searcher.search(0, &answers, &words);
+        searcher.save_cache().unwrap();
+        let mut reloaded = Searcher::new(&table, narrow);
+        reloaded.load_cache();
+        assert_eq!(reloaded.search(0, &answers, &words), narrow_best);
+        assert_eq!(reloaded.counter.node_counter.load(Relaxed), 1);
+        let mut reloaded = Searcher::new(&table, config.clone());
+        reloaded.load_cache();
+        assert_eq!(reloaded.search(0, &answers, &words).total_count, best.total_count);
+        assert!(reloaded.counter.node_counter.load(Relaxed) > 1);
+
+        let fingerprint = TranspositionTable::fingerprint(table.dictionary(), &config);
+        let other = Dictionary::new(&BTreeSet::from(["award", "beard"]), &BTreeSet::from(["award", "beard"]));
+        assert!(TranspositionTable::load_from_path(&path, fingerprint, None).is_ok());
+        assert!(TranspositionTable::load_from_path(&path, TranspositionTable::fingerprint(&other, &config), None).is_err());
+
+        let start_word = table.dictionary().id("board").unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let first = start_word_wrapper(start_word, &answers, &words, &table, &config).unwrap().0;
+        assert!(path.exists());
+        assert_eq!(start_word_wrapper(start_word, &answers, &words, &table, &config).unwrap().0, first);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_parallel_search() {
+        let rd = BTreeSet::from(["award", "beard", "board", "chard", "chord", "fjord", "gourd", "guard", "heard", "hoard", "shard", "sword", "third", "weird"]);
+        let first: BTreeSet<&str> = include_str!("../data/answers.txt").lines().take(150).collect();
+
+        for (answers, config) in [
+            (&rd, SearchConfig { max_turns: 10, exact: true, ..SearchConfig::default() }),
+            (&first, SearchConfig::default()),
+            (&first, SearchConfig { hard_mode: false, beam: Beam::Fixed(5), ..SearchConfig::default() }),
+        ] {
+            let (table, answers, words) = setup(answers, answers);
+            let sequential = Searcher::new(&table, config.clone()).search(0, &answers, &words);
+
+            // Ties go to the better ranked guess, so every thread count finds the same tree.
+            let searcher = Searcher::new(&table, SearchConfig { parallel_threshold: 1, ..config.clone() });
+            assert_eq!(searcher.search(0, &answers, &words), sequential);
+            assert!(searcher.counter.node_counter.load(Relaxed) > 1);
+
+            // Which thread fills a cache entry first varies, the baselines it gives
+            // other threads only prune and never pick the tree.
+            let cached = Searcher::new(&table, SearchConfig { parallel_threshold: 1, use_cache: true, ..config.clone() });
+            assert_eq!(cached.search(0, &answers, &words), sequential);
+            assert!(!cached.cache.is_empty());
+
+            let start_word = sequential.decision_tree.guess.unwrap();
+            let wrapped = parallel_wrapper(start_word, &answers, &words, &table, &SearchConfig { parallel_threshold: 1, ..config.clone() }).unwrap().0;
+            assert_eq!(wrapped.total_count, start_word_wrapper(start_word, &answers, &words, &table, &config).unwrap().0.total_count);
+        }
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let answers: BTreeSet<&str> = include_str!("../data/answers.txt").lines().take(60).collect();
+        let (table, answers, words) = setup(&answers, &answers);
+        let start_word = table.dictionary().id("aback").unwrap();
+
+        let path = std::env::temp_dir().join(format!("wordle-rs-checkpoint-{}.bin", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let config = SearchConfig { checkpoint_path: Some(path.clone()), ..SearchConfig::default() };
+
+        let best = parallel_wrapper(start_word, &answers, &words, &table, &config).unwrap().0;
+        assert_eq!(parallel_wrapper(start_word, &answers, &words, &table, &SearchConfig::default()).unwrap().0, best);
+
+        let fingerprint = Checkpoint::fingerprint(table.dictionary(), &config, start_word, &answers, &words);
+        let (_, done) = Checkpoint::<Task>::open(&path, fingerprint).unwrap();
+        assert!(!done.is_empty());
+
+        // A restart after everything finished searches nothing.
+        let resumed = SearchConfig { budget: Budget { nodes: Some(0), ..Budget::default() }, ..config.clone() };
+        assert_eq!(parallel_wrapper(start_word, &answers, &words, &table, &resumed).unwrap().0, best);
+
+        // Killed halfway through a record: the whole records before it are kept.
+        let len = std::fs::metadata(&path).unwrap().len();
+        std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(len / 2).unwrap();
+        let (_, kept) = Checkpoint::<Task>::open(&path, fingerprint).unwrap();
+        assert!(!kept.is_empty() && kept.len() < done.len());
+        assert!(kept.iter().all(|(task, best)| done[task] == *best));
+        assert_eq!(parallel_wrapper(start_word, &answers, &words, &table, &config).unwrap().0, best);
+
+        // Another run's file is refused and left as it is.
+        let len = std::fs::metadata(&path).unwrap().len();
+        let other = SearchConfig { beam: Beam::Fixed(3), ..config.clone() };
+        let err = Checkpoint::<Task>::open(&path, Checkpoint::fingerprint(table.dictionary(), &other, start_word, &answers, &words)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(parallel_wrapper(start_word, &answers, &words, &table, &SearchConfig { checkpoint_path: Some(path.clone()), ..other }).is_err());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+        assert!(Checkpoint::<Task>::open(std::env::temp_dir(), fingerprint).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_checkpoint_command() {
+        let answers: BTreeSet<&str> = include_str!("../data/answers.txt").lines().take(60).collect();
+        let (table, answers, words) = setup(&answers, &answers);
+        let start_word = table.dictionary().id("aback").unwrap();
+
+        let path = std::env::temp_dir().join(format!("wordle-rs-checkpoint-command-{}.bin", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let run = |args: &[&str]| {
+            let args = ["--checkpoint", path.to_str().unwrap()].into_iter().chain(args.iter().copied()).map(str::to_string);
+            let (config, rest) = SearchConfig::from_args(args).unwrap();
+            let rest: Vec<&str> = rest.iter().map(String::as_str).collect();
+            super::solve_from_start_word(&rest, start_word, &answers, &words, &table, &config).map(|(best, _)| best)
+        };
+
+        // A run stopped by its budget keeps what it finished, the next one resumes from it.
+        run(&["--node-limit", "20", "parallel"]).unwrap();
+        let fingerprint = Checkpoint::fingerprint(table.dictionary(), &SearchConfig::default(), start_word, &answers, &words);
+        let stopped = Checkpoint::<Task>::read(&path, fingerprint).unwrap();
+        let tasks = Manifest::new(start_word, &answers, &words, &table, &SearchConfig::default()).tasks.len();
+        assert!(!stopped.is_empty() && stopped.len() < tasks);
+
+        let best = run(&["parallel"]).unwrap();
+        assert_eq!(best, parallel_wrapper(start_word, &answers, &words, &table, &SearchConfig::default()).unwrap().0);
+        assert_eq!(Checkpoint::<Task>::read(&path, fingerprint).unwrap().len(), tasks);
+
+        // The other searches take no checkpoint, a run with other settings refuses the file.
+        assert_eq!(run(&[]).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(run(&["--beam", "3", "parallel"]).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_observer() {
+        // Keeps the events and cancels once told to.
+        #[derive(Debug, Default)]
+        struct Recorder {
+            events: std::sync::Mutex<Vec<String>>,
+            cancel: std::sync::atomic::AtomicBool
+        }
+
+        impl Observer for Recorder {
+            fn notify(&self, event: &Event) {
+                let event = match event {
+                    Event::Resumed { tasks } => format!("resumed {}", tasks),
+                    Event::TaskDone { total, .. } => format!("task of {}", total),
+                    Event::StageDone { name, stats } => format!("{} {}", name, stats.stages.len())
+                };
+                self.events.lock().unwrap().push(event);
+            }
+
+            fn cancelled(&self) -> bool {
+                self.cancel.load(Relaxed)
+            }
+        }
+
+        let answers: BTreeSet<&str> = include_str!("../data/answers.txt").lines().take(60).collect();
+        let (table, answers, words) = setup(&answers, &answers);
+        let start_word = table.dictionary().id("aback").unwrap();
+        let recorder = Arc::new(Recorder::default());
+        let config = SearchConfig { observer: recorder.clone(), ..SearchConfig::default() };
+
+        // Every task is reported once, each stage once it ends.
+        let (best, stats) = parallel_wrapper(start_word, &answers, &words, &table, &config).unwrap();
+        assert_eq!(best, parallel_wrapper(start_word, &answers, &words, &table, &SearchConfig::default()).unwrap().0);
+        let tasks = Manifest::new(start_word, &answers, &words, &table, &config).tasks.len();
+        let events = std::mem::take(&mut *recorder.events.lock().unwrap());
+        assert_eq!(events.iter().filter(|event| **event == format!("task of {}", tasks)).count(), tasks);
+        assert_eq!(events.iter().filter(|event| !event.starts_with("task of")).collect::<Vec<_>>(), ["plan 1", "tasks 2", "reduce 3"]);
+        assert_eq!(stats.stages.iter().map(|(name, _)| *name).collect::<Vec<_>>(), ["plan", "tasks", "reduce"]);
+        assert!(stats.nodes > 0 && stats.beam_cuts > 0 && stats.bound_prunes > 0 && !stats.truncated);
+        assert_eq!(stats.cache_hits, [0; DEPTH_SLOTS]);
+
+        // The second stage of baseline_wrapper finds what the first one cached.
+        let (_, stats) = baseline_wrapper(start_word, &answers, &words, &table, &config).unwrap();
+        assert_eq!(stats.stages.iter().map(|(name, _)| *name).collect::<Vec<_>>(), ["beam", "unlimited"]);
+        assert!(stats.cache_hits.iter().sum::<u64>() > 0 && stats.cache_misses.iter().sum::<u64>() > 0);
+        assert_eq!(stats.cache_hits[0] + stats.cache_misses[0], 0);
+
+        // A cancelled search still finishes a tree, with the first guesses it tries.
+        let (full, full_stats) = start_word_wrapper(start_word, &answers, &words, &table, &config).unwrap();
+        recorder.cancel.store(true, Relaxed);
+        let (cancelled, stats) = start_word_wrapper(start_word, &answers, &words, &table, &config).unwrap();
+        assert!(cancelled.has_result && stats.truncated);
+        assert!(cancelled.total_count >= full.total_count && stats.nodes < full_stats.nodes);
+    }
+
+    #[test]
+    fn test_sharding() {
+        let answers: BTreeSet<&str> = include_str!("../data/answers.txt").lines().take(60).collect();
+        let (table, answers, words) = setup(&answers, &answers);
+        let dictionary = table.dictionary();
+        let start_word = dictionary.id("aback").unwrap();
+        let config = SearchConfig::default();
+
+        let dir = std::env::temp_dir().join(format!("wordle-rs-shards-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let manifest = Manifest::new(start_word, &answers, &words, &table, &config);
+        manifest.save_to_path(dir.join("manifest.json"), dictionary).unwrap();
+        let manifest = Manifest::load_from_path(dir.join("manifest.json"), dictionary).unwrap();
+        assert_eq!(manifest, Manifest::new(start_word, &answers, &words, &table, &config));
+
+        let paths: Vec<_> = (0..3).map(|index| dir.join(format!("shard-{}.bin", index))).collect();
+        for (index, path) in paths.iter().enumerate() {
+            let done = manifest.solve_shard(index, 3, &answers, &words, &table, &config, path).unwrap();
+            assert_eq!(done, manifest.shard(index, 3).count());
+        }
+        assert_eq!((0..3).map(|index| manifest.shard(index, 3).count()).sum::<usize>(), manifest.tasks.len());
+
+        let merged = manifest.merge_shards(&paths, &answers, &words, &table, &config).unwrap();
+        assert_eq!(merged, parallel_wrapper(start_word, &answers, &words, &table, &config).unwrap().0);
+
+        // A missing shard or another run's settings are refused.
+        assert!(manifest.merge_shards(&paths[..2], &answers, &words, &table, &config).is_err());
+        let other = SearchConfig { beam: Beam::Fixed(3), ..config.clone() };
+        assert!(manifest.merge_shards(&paths, &answers, &words, &table, &other).is_err());
+        assert!(manifest.solve_shard(0, 3, &answers, &words, &table, &other, &paths[0]).is_err());
+
+        // A shard file of another manifest is an error and keeps its tasks.
+        let foreign = dir.join("foreign.bin");
+        Manifest::new(start_word, &answers, &words, &table, &other).solve_shard(0, 3, &answers, &words, &table, &other, &foreign).unwrap();
+        let content = std::fs::read(&foreign).unwrap();
+        let err = manifest.solve_shard(0, 3, &answers, &words, &table, &config, &foreign).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(std::fs::read(&foreign).unwrap(), content);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_leaderboard() {
+        let answers: BTreeSet<&str> = include_str!("../data/answers.txt").lines().take(60).collect();
+        let (table, answers, words) = setup(&answers, &answers);
+        let dictionary = table.dictionary();
+        let config = SearchConfig::default();
+
+        let scores = opener_scores(&table, &answers, &words, &CurrentScore, None);
+        assert_eq!(scores.len(), words.len());
+        assert!(scores.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+
+        let path = std::env::temp_dir().join(format!("wordle-rs-leaderboard-{}.bin", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let sweep = Sweep { top: 5, parallel: false, checkpoint_path: Some(path.clone()) };
+
+        let leaderboard = sweep.run(&answers, &words, &table, &config).unwrap();
+        assert_eq!(leaderboard.standings.len(), 5);
+        assert!(leaderboard.standings.windows(2).all(|pair| config.objective.compare(&pair[0].hard, &pair[1].hard) != std::cmp::Ordering::Greater));
+        for standing in leaderboard.standings.iter() {
+            assert!(standing.hard.has_result && standing.normal.has_result);
+            // A cache shared with other openers can only help.
+            let alone = start_word_wrapper(standing.word, &answers, &words, &table, &config).unwrap().0;
+            assert!(standing.hard.total_count <= alone.total_count);
+        }
+
+        let tsv = leaderboard.to_tsv(dictionary);
+        assert_eq!(tsv.lines().count(), 6);
+        assert!(tsv.lines().nth(1).unwrap().starts_with(&format!("1\t{}\t", dictionary.word(leaderboard.standings[0].word))));
+
+        // A rerun only reads the checkpoint, even with no budget left.
+        let resumed = SearchConfig { budget: Budget { nodes: Some(0), ..Budget::default() }, ..config.clone() };
+        assert_eq!(sweep.run(&answers, &words, &table, &resumed).unwrap(), leaderboard);
+
+        // A record torn by a crash is searched again.
+        let content = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &content[..content.len() - 3]).unwrap();
+        let rerun = sweep.run(&answers, &words, &table, &config).unwrap();
+        assert!(rerun.standings.iter().all(|standing| standing.hard.has_result && standing.normal.has_result));
+        assert_eq!(std::fs::read(&path).unwrap().len(), content.len());
+
+        // A sweep with other settings refuses the file and leaves it as it is.
+        let other = SearchConfig { beam: Beam::Fixed(3), ..config.clone() };
+        let err = sweep.run(&answers, &words, &table, &other).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+
+        let cached = SearchConfig { cache_path: Some(path.with_extension("cache")), ..config.clone() };
+        assert_eq!(sweep.run(&answers, &words, &table, &cached).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+
+        let parallel = Sweep { top: 2, parallel: true, checkpoint_path: None }.run(&answers, &words, &table, &config).unwrap();
+        assert_eq!(parallel.standings.len(), 2);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_heuristics() {
+        // Buckets of 1 (the win), 2 and 3 answers.
+        let mut counter = PatternCounter::new(5);
+        counter.count(&[242, 0, 0, 1, 1, 1], &[0, 1, 2, 3, 4, 5], None);
+
+        assert_eq!(CurrentScore.score(&counter, 5), 8.0);
+        assert!((ShannonEntropy.score(&counter, 5) + 1.459148).abs() < 1e-6);
+        assert_eq!(ExpectedSize.score(&counter, 5), 14.0 / 6.0);
+        assert_eq!(LargestBucket.score(&counter, 5), 3.0);
+        assert_eq!(MostParts.score(&counter, 5), -3.0);
+
+        for heuristic in all_heuristics() {
+            assert_eq!(parse_heuristic(&heuristic.name()).unwrap().name(), heuristic.name());
+        }
+        assert!(parse_heuristic("vowels").is_err());
+        let (config, _) = SearchConfig::from_args(["--heuristic", "entropy"].map(String::from)).unwrap();
+        assert_eq!(config.heuristic.name(), "entropy");
+
+        let answers: BTreeSet<&str> = include_str!("../data/answers.txt").lines().take(60).collect();
+        let (table, answers, words) = setup(&answers, &answers);
+        let config = SearchConfig { beam: Beam::Fixed(3), ..SearchConfig::default() };
+
+        let reports = compare_heuristics(&all_heuristics(), None, &answers, &words, &table, &config);
+        assert_eq!(reports.len(), 5);
+        assert_eq!(reports[0].best, Searcher::new(&table, config.clone()).search(0, &answers, &words));
+
+        let evaluator = Evaluator {
+            answers: &answers,
+            words: &words,
+            table: &table,
+            priors: None
         };
 
-        evaluator.evaluate(best.decision_tree, true);
+        for report in reports {
+            assert!(report.best.has_result && report.nodes > 0, "{} found no tree", report.name);
+            evaluator.evaluate(report.best.decision_tree, true).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_greedy_strategy() {
+        assert_eq!(Checker::parse_pattern("01010"), Some(Checker::check("crane", "salet")));
+        assert_eq!(Checker::parse_pattern("22222"), Some(242));
+        assert_eq!(Checker::parse_pattern("00x20"), None);
+
+        let answers: BTreeSet<&str> = include_str!("../data/answers.txt").lines().take(60).collect();
+        let (table, answers, words) = setup(&answers, &answers);
+        let config = SearchConfig::default();
+
+        let evaluator = Evaluator {
+            answers: &answers,
+            words: &words,
+            table: &table,
+            priors: None
+        };
+
+        // The beam always tries the greedy guess, so it never does worse.
+        let searched = Searcher::new(&table, config.clone()).search(0, &answers, &words);
+        for lookahead in [None, Some(3)] {
+            let greedy = Greedy::new(&table, config.clone(), lookahead);
+            let tree = greedy.tree(&answers, &words);
+            assert!(tree.has_result);
+            assert!(searched.total_count <= tree.total_count);
+            assert_eq!(tree.decision_tree.guess, greedy.suggest(&answers, &words));
+            evaluator.evaluate(tree.decision_tree, true).unwrap();
+        }
+
+        let greedy = Greedy::new(&table, config.clone(), None);
+        assert_eq!(greedy.name(), "greedy:score");
+        assert_eq!(Greedy::new(&table, config.clone(), Some(3)).name(), "greedy:score:3");
+        assert_eq!(greedy.suggest(&answers, &words), rank_guesses(&table, &answers, &words, 1, true, &CurrentScore, None).first().map(|(guess, _, _)| *guess));
+
+        // Small sets guess one of their own answers.
+        let pair: AnswerSet = answers.iter().take(2).collect();
+        assert!(pair.contains(greedy.suggest(&pair, &words).unwrap()));
+        let single: AnswerSet = answers.iter().take(1).collect();
+        assert_eq!(greedy.suggest(&single, &words), single.first());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_priors() {
+        assert_eq!(parse_weights("abbey\t3\n\naback\t0.5\n"), Ok(BTreeMap::from([("abbey", 3.0), ("aback", 0.5)])));
+        assert_eq!(parse_weights("abbey 3"), Err(PriorError::Malformed { line: 1, text: "abbey 3".to_string() }));
+        assert_eq!(parse_weights("abbey\t-1"), Err(PriorError::InvalidWeight { word: "abbey".to_string(), weight: "-1".to_string() }));
+
+        let answers = BTreeSet::from(["aback", "abase", "abate", "abbey", "abbot", "abhor", "abide", "abled", "abode", "abort"]);
+        let (table, answers, words) = setup(&answers, &answers);
+        let dictionary = table.dictionary();
+
+        // Weights average 1, missing answers get the lightest listed weight, other words are ignored.
+        let priors = Priors::parse("aback\t46\nabase\t2\nzzzzz\t1000\n", dictionary).unwrap();
+        assert_eq!(priors.weight(dictionary.id("aback").unwrap()), 46.0 * 10.0 / 64.0);
+        assert_eq!(priors.weight(dictionary.id("abort").unwrap()), 2.0 * 10.0 / 64.0);
+        assert_eq!(priors.mass(&answers), 10.0);
+        assert_eq!(Priors::parse("zzzzz\t1\n", dictionary), Err(PriorError::NoWeight));
+
+        let weights = parse_weights("abbey\t3\nabort\t5\nabide\t1\n").unwrap();
+        let listed = BTreeSet::from(["abbey", "abide", "abode", "abort"]);
+        assert_eq!(likely_answers(&weights, &listed, 2), BTreeSet::from(["abbey", "abort"]));
+        assert_eq!(super::parse_likely_answers("2368"), Ok(AnswerSet::CAPACITY));
+        assert!(super::parse_likely_answers("3000").is_err());
+        assert!(super::parse_likely_answers("0").is_err());
+        assert!(super::parse_likely_answers("many").is_err());
+
+        // Buckets weigh by mass: the heavy answer alone outweighs the other three.
+        let weighted = Priors::from_weights(vec![5.0, 1.0, 1.0, 1.0]).unwrap();
+        let mut counter = PatternCounter::new(5);
+        counter.count(&[0, 1, 1, 1], &[0, 1, 2, 3], Some(&weighted));
+        assert_eq!(LargestBucket.score(&counter, 5), 2.5);
+        assert_eq!(CurrentScore.score(&counter, 5), 2.5 + 5.0 * 0.5);
+        counter.count(&[0, 1, 1, 1], &[0, 1, 2, 3], None);
+        assert_eq!(LargestBucket.score(&counter, 5), 3.0);
+
+        let config = SearchConfig { beam: Beam::Unlimited, objective: Arc::new(ExpectedGuesses), ..SearchConfig::default() };
+        let uniform = Searcher::new(&table, config.clone()).search(0, &answers, &words);
+        let prioritised = SearchConfig { priors: Some(Arc::new(priors.clone())), ..config.clone() };
+        let expected = Searcher::new(&table, prioritised.clone()).search(0, &answers, &words);
+        assert_eq!(uniform.weighted_count, uniform.total_count as f64);
+        // The likely answer is guessed first.
+        assert_eq!(expected.decision_tree.guess, dictionary.id("aback").ok());
+        assert!(expected.weighted_count < uniform.weighted_count);
+        assert_ne!(TranspositionTable::fingerprint(dictionary, &config), TranspositionTable::fingerprint(dictionary, &prioritised));
+
+        let evaluator = Evaluator {
+            answers: &answers,
+            words: &words,
+            table: &table,
+            priors: Some(&priors)
+        };
+
+        let evaluation = evaluator.evaluate(expected.decision_tree.clone(), true).unwrap();
+        assert_eq!(evaluation.total, expected.total_count);
+        assert_eq!(evaluation.plays.len(), answers.len());
+        assert_eq!(evaluation.plays.iter().map(|(_, played)| played.len() as u32).sum::<u32>(), evaluation.total);
+        assert!((evaluation.weighted_average - expected.weighted_count / answers.len() as f64).abs() < 1e-9);
+        assert!(evaluation.weighted_average < evaluator.evaluate(uniform.decision_tree, true).unwrap().weighted_average);
+    }
+
+    #[test]
+    fn test_decision_tree_files() {
+        assert_eq!(Checker::format_pattern(Checker::check("crane", "salet"), 5), "01010");
+        assert_eq!(Checker::parse_pattern(&Checker::format_pattern(162, 5)), Some(162));
+
+        let answers = BTreeSet::from(["aback", "abase", "abate", "abbey", "abbot", "abhor", "abide", "abled", "abode", "abort"]);
+        let (table, answers, words) = setup(&answers, &answers);
+        let dictionary = table.dictionary();
+        let best = Searcher::new(&table, SearchConfig::default()).search(0, &answers, &words);
+
+        let path = std::env::temp_dir().join(format!("wordle-tree-{}.json", std::process::id()));
+        best.decision_tree.save_to_path(&path, dictionary).unwrap();
+        let loaded = DecisionTree::load_from_path(&path, dictionary).unwrap();
+        assert_eq!(loaded, best.decision_tree);
+        std::fs::remove_file(&path).unwrap();
+
+        let evaluator = Evaluator {
+            answers: &answers,
+            words: &words,
+            table: &table,
+            priors: None
+        };
+        assert_eq!(evaluator.evaluate(loaded, true).unwrap().total, best.total_count);
+
+        // The shipped tree reads back into the JSON it was written from.
+        let full = Dictionary::from_lists(include_str!("../data/answers.txt"), include_str!("../data/words.txt")).unwrap();
+        let shipped = include_str!("../data/wordle.json");
+        let tree = DecisionTree::from_json(shipped, &full).unwrap();
+        assert_eq!(tree.guess, full.id("salet").ok());
+        let written: serde_json::Value = serde_json::from_str(&tree.to_json(&full)).unwrap();
+        assert_eq!(written, serde_json::from_str::<serde_json::Value>(shipped).unwrap());
+
+        let error = DecisionTree::from_json(&shipped.replacen("\"drony\"", "\"xxxxx\"", 1), &full).unwrap_err();
+        assert_eq!(error.to_string(), "tree has an unknown word \"xxxxx\" at salet:00000");
+        let error = DecisionTree::from_json(r#"{"guess":"salet","branch":{"243":{"guess":"","branch":{}}}}"#, &full).unwrap_err();
+        assert_eq!(error.to_string(), "tree has pattern 243 after salet at the root");
+        let error = DecisionTree::from_json(r#"{"guess":"","branch":{"0":{"guess":"","branch":{}}}}"#, &full).unwrap_err();
+        assert_eq!(error.to_string(), "tree has branches but no guess at the root");
+        assert!(DecisionTree::from_json("[]", &full).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let answers = BTreeSet::from(["aback", "abase", "abate", "abbey", "abbot", "abhor", "abide", "abled", "abode", "abort"]);
+        let words = BTreeSet::from(["aback", "abase", "abate", "abbey", "abbot", "abhor", "abide", "abled", "abode", "abort", "salet"]);
+        let (table, answers, words) = setup(&answers, &words);
+        let dictionary = table.dictionary();
+        let id = |word: &str| dictionary.id(word).unwrap();
+
+        let best = Searcher::new(&table, SearchConfig::default()).search(0, &answers, &words);
+        let report = validate(&best.decision_tree, &answers, &words, &table, true, 6);
+        assert!(report.is_valid());
+        assert_eq!((report.answer_count, report.solved), (10, 10));
+
+        // Three guesses find some answers, a cap of two reports each of them.
+        let capped = validate(&best.decision_tree, &answers, &words, &table, true, 2);
+        assert_eq!(capped.issues.len() as u32, best.depth_counts[3]);
+        assert!(capped.is_playable());
+        assert!(capped.issues.iter().all(|issue| matches!(issue.problem, Problem::TooDeep { turns: 3, .. }) && issue.path.len() == 3));
+
+        // aback then salet, which hard mode rules out once aback shows its a and b.
+        let mut tree = DecisionTree::from(id("aback"), BTreeMap::new());
+        let aback_abase = Checker::check("abase", "aback");
+        let salet_abase = Checker::check("abase", "salet");
+        tree.add_branch(Checker::success_pattern(5), DecisionTree::new());
+        tree.add_branch(aback_abase, DecisionTree::from(id("salet"), BTreeMap::from([(0, DecisionTree::new())])));
+
+        let report = validate(&tree, &answers, &words, &table, true, 6);
+        assert!(!report.is_valid() && !report.is_playable());
+        assert_eq!(report.solved, 1);
+
+        assert!(report.issues.contains(&Issue { path: vec![(id("aback"), aback_abase)], problem: Problem::IllegalGuess { guess: id("salet") } }));
+        assert!(report.issues.contains(&Issue { path: vec![(id("aback"), aback_abase)], problem: Problem::ImpossiblePattern { pattern: 0 } }));
+        assert!(report.issues.iter().any(|issue| issue.path == vec![(id("aback"), aback_abase), (id("salet"), salet_abase)] && matches!(&issue.problem, Problem::Unsolved { answers } if answers.contains(&id("abase")))));
+        let unsolved: usize = report.issues.iter().map(|issue| match &issue.problem {
+            Problem::Unsolved { answers } => answers.len(),
+            _ => 0
+        }).sum();
+        assert_eq!(unsolved + report.solved, 10);
+
+        // Normal mode allows salet anywhere.
+        let normal = validate(&tree, &answers, &words, &table, false, 6);
+        assert!(!normal.issues.iter().any(|issue| matches!(issue.problem, Problem::IllegalGuess { .. })));
+
+        // evaluate reports what stops it instead of panicking.
+        let evaluator = Evaluator { answers: &answers, words: &words, table: &table, priors: None };
+        assert_eq!(evaluator.evaluate(tree.clone(), true), Err(validate(&tree, &answers, &words, &table, true, u32::MAX)));
+
+        // Words with no letter in common are told apart one guess at a time.
+        let letters: Vec<String> = ('a'..='l').map(|letter| letter.to_string().repeat(5)).collect();
+        let chain: BTreeSet<&str> = letters.iter().map(String::as_str).collect();
+        let (chain_table, chain_answers, chain_words) = setup(&chain, &chain);
+        let mut deep = DecisionTree::new();
+        for word in letters.iter().rev() {
+            let mut branch = BTreeMap::from([(Checker::success_pattern(5), DecisionTree::new())]);
+            if deep.guess.is_some() {
+                branch.insert(0, deep);
+            }
+            deep = DecisionTree::from(chain_table.dictionary().id(word).unwrap(), branch);
+        }
+        let evaluator = Evaluator { answers: &chain_answers, words: &chain_words, table: &chain_table, priors: None };
+        let evaluation = evaluator.evaluate(deep, true).unwrap();
+        assert_eq!((evaluation.max_turn, evaluation.total), (12, 78));
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json(dictionary).unwrap()).unwrap();
+        assert_eq!(json["valid"], false);
+        let illegal = json["issues"].as_array().unwrap().iter().find(|issue| issue["kind"] == "illegal_guess").unwrap();
+        assert_eq!(illegal["guess"], "salet");
+        assert_eq!(illegal["path"], serde_json::json!([format!("aback:{}", Checker::format_pattern(aback_abase, 5))]));
+    }
+
+    #[test]
+    fn test_binary_tree_format() {
+        let full = Dictionary::from_lists(include_str!("../data/answers.txt"), include_str!("../data/words.txt")).unwrap();
+        let shipped = include_str!("../data/wordle.json");
+        let tree = DecisionTree::from_json(shipped, &full).unwrap();
+
+        let bytes = tree.to_binary(&full);
+        assert!(bytes.len() * 10 < shipped.len());
+        let decoded = DecisionTree::from_binary(&bytes, &full).unwrap();
+        assert_eq!(decoded, tree);
+        let written: serde_json::Value = serde_json::from_str(&decoded.to_json(&full)).unwrap();
+        assert_eq!(written, serde_json::from_str::<serde_json::Value>(shipped).unwrap());
+
+        // load_from_path tells the formats apart.
+        let path = std::env::temp_dir().join(format!("wordle-tree-{}.bin", std::process::id()));
+        tree.save_binary_to_path(&path, &full).unwrap();
+        assert_eq!(DecisionTree::load_from_path(&path, &full).unwrap(), tree);
+        std::fs::remove_file(&path).unwrap();
+
+        let error = |bytes: &[u8], dictionary: &Dictionary| DecisionTree::from_binary(bytes, dictionary).unwrap_err().to_string();
+        let other = Dictionary::new(&BTreeSet::from(["salet"]), &BTreeSet::from(["salet"]));
+        assert_eq!(error(&bytes, &other), "decision tree was written for different word lists");
+        assert_eq!(error(&bytes[1..], &full), "not a decision tree file");
+        assert_eq!(error(&[bytes.as_slice(), &[0]].concat(), &full), "decision tree file has trailing bytes");
+        assert_eq!(DecisionTree::from_binary(&bytes[..bytes.len() - 1], &full).unwrap_err().kind(), std::io::ErrorKind::UnexpectedEof);
+
+        // Trees the search never builds still round trip: a guessless node on an
+        // ordinary pattern and a success pattern that leads somewhere.
+        let odd = DecisionTree::from(3, BTreeMap::from([
+            (0, DecisionTree::new()),
+            (242, DecisionTree::from(4, BTreeMap::new()))
+        ]));
+        assert_eq!(DecisionTree::from_binary(&odd.to_binary(&full), &full).unwrap(), odd);
+        assert_eq!(DecisionTree::from_binary(&DecisionTree::new().to_binary(&full), &full).unwrap(), DecisionTree::new());
+
+        // Eight letters take two bytes a pattern.
+        let words = BTreeSet::from(["abcdefgh", "abcdefgz", "zbcdefgh"]);
+        let (table, answers, words) = setup(&words, &words);
+        let long = Searcher::new(&table, SearchConfig::default()).search(0, &answers, &words).decision_tree;
+        assert!(long.branch.keys().any(|pattern| *pattern > 255));
+        assert_eq!(DecisionTree::from_binary(&long.to_binary(table.dictionary()), table.dictionary()).unwrap(), long);
+    }
+
+    #[test]
+    fn test_export() {
+        assert_eq!(pattern_label(Checker::check("crane", "salet"), 5, false), "BYBYB");
+        assert_eq!(pattern_label(Checker::check("salts", "salet"), 5, true), "🟩🟩🟩⬛🟨");
+
+        let answers = BTreeSet::from(["aback", "abase", "abate", "abbey", "abbot", "abhor", "abide", "abled", "abode", "abort"]);
+        let (table, answers, words) = setup(&answers, &answers);
+        let dictionary = table.dictionary();
+        let tree = Searcher::new(&table, SearchConfig::default()).search(0, &answers, &words).decision_tree;
+        let root = dictionary.word(tree.guess.unwrap());
+
+        // Every answer but the root's own guess ends below it, one node per guess.
+        let dot = to_dot(&tree, &answers, &table, &ExportOptions::default()).unwrap();
+        assert!(dot.starts_with("digraph tree {\n") && dot.ends_with("}\n"));
+        assert!(dot.contains(&format!("    n0 [label=\"{}\\n10\"];\n", root)));
+        let nodes = dot.lines().filter(|line| line.contains("[label=") && !line.contains("->")).count();
+        let edges = dot.lines().filter(|line| line.contains("->")).count();
+        assert_eq!(nodes, edges + 1);
+        assert!(!dot.contains("dashed"));
+
+        // Depth 0 draws the root alone, cut off.
+        let root_only = ExportOptions { max_depth: Some(0), ..ExportOptions::default() };
+        assert_eq!(to_dot(&tree, &answers, &table, &root_only).unwrap(), format!("digraph tree {{\n    node [shape=box, fontname=\"monospace\"];\n    n0 [label=\"{}\\n10\", style=dashed];\n}}\n", root));
+        assert_eq!(to_mermaid(&tree, &answers, &table, &root_only).unwrap(), format!("flowchart TD\n    n0[\"{}<br/>10 …\"]\n", root));
+
+        // Depth 1 adds the guesses after the root, those with more below are cut off.
+        let shallow = ExportOptions { max_depth: Some(1), ..ExportOptions::default() };
+        let dot = to_dot(&tree, &answers, &table, &shallow).unwrap();
+        let children = tree.branch.keys().filter(|pattern| !Checker::is_success_pattern(**pattern, 5)).count();
+        let cut_off = tree.branch.values().filter(|subtree| subtree.branch.keys().any(|pattern| !Checker::is_success_pattern(*pattern, 5))).count();
+        assert_eq!(dot.lines().filter(|line| line.contains("->")).count(), children);
+        assert_eq!(dot.lines().filter(|line| line.contains("dashed")).count(), cut_off);
+        assert!(cut_off > 0 && dot.contains(&format!("    n0 [label=\"{}\\n10\"];\n", root)));
+        assert_eq!(to_dot(&tree, &answers, &table, &ExportOptions { max_depth: Some(9), ..ExportOptions::default() }).unwrap(), to_dot(&tree, &answers, &table, &ExportOptions::default()).unwrap());
+
+        // A path draws the subtree after it, counted from the answers left there.
+        let (pattern, subtree) = tree.branch.iter().find(|(_, subtree)| subtree.branch.keys().any(|pattern| !Checker::is_success_pattern(*pattern, 5))).unwrap();
+        let subtree_answers = filter_available_answers(&table, tree.guess.unwrap(), *pattern, &answers);
+        let options = ExportOptions { path: vec![(tree.guess.unwrap(), *pattern)], squares: true, ..ExportOptions::default() };
+        let mermaid = to_mermaid(&tree, &answers, &table, &options).unwrap();
+        assert!(mermaid.starts_with(&format!("flowchart TD\n    n0[\"{}<br/>{}\"]\n", dictionary.word(subtree.guess.unwrap()), subtree_answers.len())));
+        assert!(mermaid.contains("-->|\"") && mermaid.contains('🟩'));
+
+        let other = words.iter().find(|word| Some(**word) != tree.guess).unwrap();
+        let wrong = ExportOptions { path: vec![(*other, 0)], ..ExportOptions::default() };
+        assert_eq!(to_dot(&tree, &answers, &table, &wrong).unwrap_err(), format!("the tree does not play {} there", dictionary.word(*other)));
+        let unused = (0..243).find(|pattern| !tree.branch.contains_key(pattern)).unwrap();
+        let missing = ExportOptions { path: vec![(tree.guess.unwrap(), unused)], ..ExportOptions::default() };
+        assert_eq!(to_dot(&tree, &answers, &table, &missing).unwrap_err(), format!("the tree has no branch for {} after {}", pattern_label(unused, 5, false), root));
+    }
+
+    #[test]
+    fn test_cheat_sheet() {
+        let answers = BTreeSet::from(["aback", "abase", "abate", "abbey", "abbot", "abhor", "abide", "abled", "abode", "abort"]);
+        let (table, answers, words) = setup(&answers, &answers);
+        let dictionary = table.dictionary();
+        let tree = Searcher::new(&table, SearchConfig::default()).search(0, &answers, &words).decision_tree;
+        let root = tree.guess.unwrap();
+
+        // One group per pattern of the opener that does not win, every other answer listed.
+        let text = cheat_sheet(&tree, &answers, &table, &SheetOptions::default());
+        assert!(text.starts_with(&format!("Open with {} (10 answers)\n\n", dictionary.word(root))));
+        let groups = tree.branch.keys().filter(|pattern| !Checker::is_success_pattern(**pattern, 5)).count();
+        assert_eq!(text.lines().filter(|line| line.contains(" → ")).count(), groups);
+        for answer in answers.iter().filter(|answer| *answer != root) {
+            assert!(text.contains(dictionary.word(answer)));
+        }
+        assert!(!text.contains('\x1b') && !text.contains("rare"));
+
+        let colored = cheat_sheet(&tree, &answers, &table, &SheetOptions { color: true, ..SheetOptions::default() });
+        let (pattern, _) = tree.branch.iter().find(|(pattern, _)| !Checker::is_success_pattern(**pattern, 5)).unwrap();
+        assert!(colored.contains(&stat_color(dictionary.word(root), *pattern)));
+
+        // Every branch is rare with more answers than the opener leaves in any group.
+        let all_rare = SheetOptions { markdown: true, rare: 11, ..SheetOptions::default() };
+        let markdown = cheat_sheet(&tree, &answers, &table, &all_rare);
+        assert!(markdown.starts_with(&format!("# Open with {} (10 answers)\n\n", dictionary.word(root))));
+        assert_eq!(markdown.matches("<details><summary>").count(), groups);
+        assert_eq!(markdown.matches("</details>").count(), groups);
+        assert!(!markdown.contains("## ") && markdown.contains("| Path | Next guess | Left | Answers |") && markdown.contains('⬛'));
+
+        let shown = cheat_sheet(&tree, &answers, &table, &SheetOptions { markdown: true, ..SheetOptions::default() });
+        assert_eq!(shown.matches("\n## ").count(), groups);
+        assert!(!shown.contains("<details>"));
+
+        let short = cheat_sheet(&tree, &answers, &table, &SheetOptions { list_limit: Some(0), ..SheetOptions::default() });
+        assert!(short.lines().filter(|line| line.starts_with("    ")).all(|line| line.ends_with(" more")));
+    }
+}
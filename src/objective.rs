@@ -0,0 +1,169 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::common::Best;
+
+// What the search minimises. Implementations must only get worse when an answer
+// moves deeper in the tree, the pruning bounds rely on it.
+pub trait Objective: fmt::Debug + Send + Sync {
+    // Stable identifier, used to tell results of different objectives apart.
+    fn name(&self) -> String;
+
+    // Less when `a` is the better of two finished results.
+    fn compare(&self, a: &Best, b: &Best) -> Ordering;
+
+    // The quantity compared first, optimality gaps are measured in it.
+    fn score(&self, best: &Best) -> f64;
+}
+
+fn compare_f64(a: f64, b: f64) -> Ordering {
+    if (a - b).abs() <= 1e-9 * a.abs().max(b.abs()).max(1.0) {
+        Ordering::Equal
+    } else {
+        a.total_cmp(&b)
+    }
+}
+
+// Fewest total guesses, then the shallowest tree.
+#[derive(Debug, Clone, Default)]
+pub struct TotalGuesses;
+
+impl Objective for TotalGuesses {
+    fn name(&self) -> String {
+        "total".to_string()
+    }
+
+    fn compare(&self, a: &Best, b: &Best) -> Ordering {
+        a.total_count.cmp(&b.total_count).then(a.max_level.cmp(&b.max_level))
+    }
+
+    fn score(&self, best: &Best) -> f64 {
+        best.total_count as f64
+    }
+}
+
+// Shallowest tree, then fewest total guesses.
+#[derive(Debug, Clone, Default)]
+pub struct WorstCase;
+
+impl Objective for WorstCase {
+    fn name(&self) -> String {
+        "worst".to_string()
+    }
+
+    fn compare(&self, a: &Best, b: &Best) -> Ordering {
+        a.max_level.cmp(&b.max_level).then(a.total_count.cmp(&b.total_count))
+    }
+
+    fn score(&self, best: &Best) -> f64 {
+        best.max_level as f64
+    }
+}
+
+// Fewest answers needing more than `turns` guesses, then fewest total guesses.
+#[derive(Debug, Clone)]
+pub struct FewestAbove {
+    pub turns: u8
+}
+
+impl FewestAbove {
+    fn count(&self, best: &Best) -> u32 {
+        best.depth_counts.iter().skip(self.turns as usize + 1).sum()
+    }
+}
+
+impl Objective for FewestAbove {
+    fn name(&self) -> String {
+        format!("above:{}", self.turns)
+    }
+
+    fn compare(&self, a: &Best, b: &Best) -> Ordering {
+        self.count(a).cmp(&self.count(b))
+            .then(a.total_count.cmp(&b.total_count))
+            .then(a.max_level.cmp(&b.max_level))
+    }
+
+    fn score(&self, best: &Best) -> f64 {
+        self.count(best) as f64
+    }
+}
+
+// Expected number of guesses with answers drawn by SearchConfig::priors,
+// uniformly without them, scaled by the answer count.
+#[derive(Debug, Clone, Default)]
+pub struct ExpectedGuesses;
+
+impl Objective for ExpectedGuesses {
+    fn name(&self) -> String {
+        "expected".to_string()
+    }
+
+    fn compare(&self, a: &Best, b: &Best) -> Ordering {
+        compare_f64(a.weighted_count, b.weighted_count)
+            .then(a.max_level.cmp(&b.max_level))
+            .then(a.total_count.cmp(&b.total_count))
+    }
+
+    fn score(&self, best: &Best) -> f64 {
+        best.weighted_count
+    }
+}
+
+// Each answer found with d guesses costs costs[d - 1], depths past the end reuse
+// the last cost. Costs must not decrease with depth.
+#[derive(Debug, Clone)]
+pub struct DepthCost {
+    pub costs: Vec<f64>
+}
+
+impl DepthCost {
+    fn cost(&self, best: &Best) -> f64 {
+        best.depth_counts.iter().enumerate().skip(1).map(|(depth, count)| {
+            let cost = self.costs.get(depth - 1).or_else(|| self.costs.last()).cloned().unwrap_or(0.0);
+            cost * *count as f64
+        }).sum()
+    }
+}
+
+impl Objective for DepthCost {
+    fn name(&self) -> String {
+        let costs: Vec<_> = self.costs.iter().map(|cost| cost.to_string()).collect();
+        format!("cost:{}", costs.join(","))
+    }
+
+    fn compare(&self, a: &Best, b: &Best) -> Ordering {
+        compare_f64(self.cost(a), self.cost(b)).then(a.total_count.cmp(&b.total_count))
+    }
+
+    fn score(&self, best: &Best) -> f64 {
+        self.cost(best)
+    }
+}
+
+// "total", "worst", "expected", "above:5" or "cost:1,2,3,4,5,8".
+pub fn parse_objective(s: &str) -> Result<Box<dyn Objective>, String> {
+    let (name, value) = s.split_once(':').unwrap_or((s, ""));
+
+    match name {
+        "total" => Ok(Box::new(TotalGuesses)),
+        "worst" => Ok(Box::new(WorstCase)),
+        "expected" => Ok(Box::new(ExpectedGuesses)),
+        "above" => value
+            .parse()
+            .map(|turns| Box::new(FewestAbove { turns }) as Box<dyn Objective>)
+            .map_err(|_| format!("invalid objective {:?}", s)),
+        "cost" => {
+            let costs = value
+                .split(',')
+                .map(|cost| cost.trim().parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| format!("invalid objective {:?}", s))?;
+            if costs.windows(2).any(|pair| pair[0] > pair[1]) {
+                return Err(format!("depth costs must not decrease in {:?}", s));
+            }
+            Ok(Box::new(DepthCost { costs }))
+        },
+        _ => Err(format!("unknown objective {:?}", s))
+    }
+}
+
@@ -0,0 +1,66 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::common::DEPTH_SLOTS;
+
+// What a wrapper did, returned next to its tree.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchStats {
+    // Calls to Searcher::search.
+    pub nodes: u64,
+    // Nodes whose beam left out at least one ranked guess.
+    pub beam_cuts: u64,
+    // Guesses given up because a bound showed they could not beat a sibling.
+    pub bound_prunes: u64,
+    // Solver cache lookups by the number of the guess searched. A baseline is a
+    // miss, the node is still searched.
+    pub cache_hits: [u64; DEPTH_SLOTS],
+    pub cache_misses: [u64; DEPTH_SLOTS],
+    // Wall time of each stage, in the order they ran.
+    pub stages: Vec<(&'static str, Duration)>,
+    // The budget ran out or the observer cancelled, the tree is not the best the beam finds.
+    pub truncated: bool
+}
+
+impl fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hits: u64 = self.cache_hits.iter().sum();
+        let misses: u64 = self.cache_misses.iter().sum();
+        write!(f, "{} nodes, {} beam cuts, {} bound prunes, {} cache hits, {} misses", self.nodes, self.beam_cuts, self.bound_prunes, hits, misses)?;
+        for (name, elapsed) in self.stages.iter() {
+            write!(f, ", {} {:.2}s", name, elapsed.as_secs_f64())?;
+        }
+        if self.truncated {
+            write!(f, ", truncated")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum Event<'a> {
+    // parallel_wrapper found this many tasks already in its checkpoint.
+    Resumed { tasks: usize },
+    // Another parallel_wrapper task finished, `done` of the `total` it runs.
+    TaskDone { done: usize, total: usize },
+    // A wrapper stage ended, `stats` covers the run up to here.
+    StageDone { name: &'static str, stats: &'a SearchStats }
+}
+
+// Watches a search from SearchConfig::observer. Events come from whichever
+// thread they happened on.
+pub trait Observer: fmt::Debug + Send + Sync {
+    fn notify(&self, _event: &Event) {}
+
+    // Polled with the budget. Once true the search finishes as if the budget ran
+    // out: each open subtree keeps the first tree it finds.
+    fn cancelled(&self) -> bool {
+        false
+    }
+}
+
+// Ignores every event and never cancels.
+#[derive(Debug, Clone, Default)]
+pub struct Silent;
+
+impl Observer for Silent {}
@@ -0,0 +1,134 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::answer_set::AnswerSet;
+use crate::dictionary::{Dictionary, WordId};
+use crate::encoding::StableHasher;
+
+#[derive(Debug, PartialEq)]
+pub enum PriorError {
+    Malformed { line: usize, text: String },
+    InvalidWeight { word: String, weight: String },
+    // No answer of the dictionary has a positive weight.
+    NoWeight
+}
+
+impl fmt::Display for PriorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PriorError::Malformed { line, text } => write!(f, "line {} is not word<TAB>weight: {:?}", line, text),
+            PriorError::InvalidWeight { word, weight } => write!(f, "word {:?} has invalid weight {:?}", word, weight),
+            PriorError::NoWeight => write!(f, "no answer has a positive weight")
+        }
+    }
+}
+
+impl std::error::Error for PriorError {}
+
+// Parse "word<TAB>weight" lines, e.g. a word frequency list. Weights must be
+// finite and not negative, a word listed twice keeps its last weight.
+pub fn parse_weights(content: &str) -> Result<BTreeMap<&str, f64>, PriorError> {
+    let mut weights = BTreeMap::new();
+
+    for (index, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let (word, weight) = line.split_once('\t').ok_or_else(|| PriorError::Malformed { line: index + 1, text: line.to_string() })?;
+        let (word, weight) = (word.trim(), weight.trim());
+
+        match weight.parse::<f64>() {
+            Ok(value) if value.is_finite() && value >= 0.0 => { weights.insert(word, value); },
+            _ => return Err(PriorError::InvalidWeight { word: word.to_string(), weight: weight.to_string() })
+        }
+    }
+
+    Ok(weights)
+}
+
+// The `limit` heaviest words of `words`, for clones that publish a guess list
+// but no answer list. Ties go to the word first in alphabetical order.
+pub fn likely_answers<'a>(weights: &BTreeMap<&str, f64>, words: &BTreeSet<&'a str>, limit: usize) -> BTreeSet<&'a str> {
+    let mut ranked: Vec<_> = words.iter().map(|word| (*word, weights.get(word).cloned().unwrap_or(0.0))).collect();
+    ranked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    ranked.into_iter().take(limit).map(|(word, _)| word).collect()
+}
+
+// How likely each answer is, by answer id. Weights are scaled to average 1 so
+// weighted_count stays comparable to total_count, uniform priors make them equal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Priors {
+    weights: Vec<f64>
+}
+
+impl Priors {
+    pub fn uniform(answer_count: usize) -> Self {
+        Priors {
+            weights: vec![1.0; answer_count]
+        }
+    }
+
+    pub fn from_weights(weights: Vec<f64>) -> Result<Self, PriorError> {
+        if let Some((id, weight)) = weights.iter().enumerate().find(|(_, weight)| !weight.is_finite() || **weight < 0.0) {
+            return Err(PriorError::InvalidWeight { word: format!("answer {}", id), weight: weight.to_string() });
+        }
+
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return Err(PriorError::NoWeight);
+        }
+
+        let scale = weights.len() as f64 / total;
+        Ok(Priors {
+            weights: weights.into_iter().map(|weight| weight * scale).collect()
+        })
+    }
+
+    // Weights of the dictionary's answers from parse_weights. Words that are not
+    // answers are ignored, answers missing from the file get the smallest weight
+    // it gives any answer, rare words are the ones frequency lists leave out.
+    pub fn from_map(weights: &BTreeMap<&str, f64>, dictionary: &Dictionary) -> Result<Self, PriorError> {
+        let answers: Vec<_> = (0..dictionary.answer_count() as WordId).map(|id| weights.get(dictionary.word(id)).cloned()).collect();
+        let missing = answers.iter().flatten().cloned().reduce(f64::min).ok_or(PriorError::NoWeight)?;
+
+        Priors::from_weights(answers.into_iter().map(|weight| weight.unwrap_or(missing)).collect())
+    }
+
+    pub fn parse(content: &str, dictionary: &Dictionary) -> Result<Self, PriorError> {
+        Priors::from_map(&parse_weights(content)?, dictionary)
+    }
+
+    pub fn load_from_path<P: AsRef<Path>>(path: P, dictionary: &Dictionary) -> io::Result<Self> {
+        Priors::parse(&fs::read_to_string(path)?, dictionary).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    pub fn weight(&self, answer: WordId) -> f64 {
+        self.weights[answer as usize]
+    }
+
+    // Summed weight of a set of answers.
+    pub fn mass(&self, answers: &AnswerSet) -> f64 {
+        answers.iter().map(|id| self.weight(id)).sum()
+    }
+
+    pub fn heaviest(&self, answers: &AnswerSet) -> f64 {
+        answers.iter().map(|id| self.weight(id)).fold(0.0, f64::max)
+    }
+
+    // Identifies the weights in cache and checkpoint headers.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = StableHasher::new(self.weights.len() as u64);
+        for weight in self.weights.iter() {
+            hasher.add(weight.to_bits());
+        }
+        hasher.0
+    }
+}
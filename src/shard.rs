@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{Best, Pattern, Task};
+use crate::config::SearchConfig;
+use crate::dfs::Searcher;
+use crate::table::PatternTable;
+use crate::dictionary::{Dictionary, WordId};
+use crate::answer_set::AnswerSet;
+use crate::checkpoint::Checkpoint;
+use crate::wrapper::TaskPlan;
+
+const VERSION: u8 = 1;
+
+// parallel_wrapper split across processes. The manifest lists its tasks once,
+// each process solves a shard of them into a Checkpoint file, and merge_shards
+// reduces the files exactly as parallel_wrapper reduces its own results.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    // Checkpoint::fingerprint of the run, every shard and the merge check it.
+    pub fingerprint: u64,
+    pub start_word: WordId,
+    pub tasks: Vec<Task>
+}
+
+// On disk the words are spelled out, so a manifest can be read and split by hand.
+#[derive(Serialize, Deserialize)]
+struct ManifestFile {
+    version: u8,
+    fingerprint: u64,
+    start_word: String,
+    tasks: Vec<(Pattern, String, Pattern)>
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Manifest {
+    pub fn new(start_word: WordId, answers: &AnswerSet, availables: &BTreeSet<WordId>, table: &PatternTable, config: &SearchConfig) -> Self {
+        let searcher = Searcher::new(table, config.clone());
+        let plan = TaskPlan::new(&searcher, start_word, answers, availables);
+
+        Manifest {
+            fingerprint: Checkpoint::fingerprint(table.dictionary(), config, start_word, answers, availables),
+            start_word,
+            tasks: plan.tasks.into_iter().collect()
+        }
+    }
+
+    pub fn save_to_path<P: AsRef<Path>>(&self, path: P, dictionary: &Dictionary) -> io::Result<()> {
+        let file = ManifestFile {
+            version: VERSION,
+            fingerprint: self.fingerprint,
+            start_word: dictionary.word(self.start_word).to_string(),
+            tasks: self.tasks.iter().map(|(pattern, second_guess, second_pattern)| {
+                (*pattern, dictionary.word(*second_guess).to_string(), *second_pattern)
+            }).collect()
+        };
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, &file)?;
+        writer.flush()
+    }
+
+    pub fn load_from_path<P: AsRef<Path>>(path: P, dictionary: &Dictionary) -> io::Result<Self> {
+        let file: ManifestFile = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        if file.version != VERSION {
+            return Err(invalid(format!("unsupported manifest version {}", file.version)));
+        }
+
+        let id = |word: &str| dictionary.id(word).map_err(|err| invalid(format!("manifest has an {}", err)));
+
+        Ok(Manifest {
+            fingerprint: file.fingerprint,
+            start_word: id(&file.start_word)?,
+            tasks: file.tasks.iter().map(|(pattern, second_guess, second_pattern)| {
+                Ok((*pattern, id(second_guess)?, *second_pattern))
+            }).collect::<io::Result<_>>()?
+        })
+    }
+
+    // Tasks `index`, `index + count`, ..., interleaved so every shard gets easy and hard patterns.
+    pub fn shard(&self, index: usize, count: usize) -> impl Iterator<Item = &Task> {
+        self.tasks.iter().skip(index).step_by(count.max(1))
+    }
+
+    // Fails unless the run matches the one the manifest was written for.
+    fn check(&self, answers: &AnswerSet, availables: &BTreeSet<WordId>, table: &PatternTable, config: &SearchConfig) -> io::Result<()> {
+        if Checkpoint::fingerprint(table.dictionary(), config, self.start_word, answers, availables) != self.fingerprint {
+            return Err(invalid("manifest was written for different word lists or settings".to_string()));
+        }
+        Ok(())
+    }
+
+    // Solves shard `index` of `count` into the checkpoint file at `path`. A shard that
+    // was stopped resumes from the file, returns how many of its tasks are done.
+    #[allow(clippy::too_many_arguments)]
+    pub fn solve_shard<P: AsRef<Path>>(&self, index: usize, count: usize, answers: &AnswerSet, availables: &BTreeSet<WordId>, table: &PatternTable, config: &SearchConfig, path: P) -> io::Result<usize> {
+        self.check(answers, availables, table, config)?;
+
+        let searcher = Searcher::new(table, config.clone());
+        let plan = TaskPlan::new(&searcher, self.start_word, answers, availables);
+        let (checkpoint, done) = Checkpoint::<Task>::open(path, self.fingerprint)?;
+
+        let tasks: Vec<_> = self.shard(index, count).filter(|task| !done.contains_key(task)).collect();
+        let recorded = tasks.par_iter().map(|task| {
+            let best = plan.solve(&searcher, task, answers, availables);
+            // Cut short by the budget, left for a later run of the shard.
+            if searcher.clock.exhausted() {
+                return Ok(0);
+            }
+            checkpoint.record(task, &best).map(|_| 1)
+        }).sum::<io::Result<usize>>()?;
+
+        Ok(self.shard(index, count).filter(|task| done.contains_key(task)).count() + recorded)
+    }
+
+    // Reduces the shard files into the tree parallel_wrapper would have found.
+    // Fails when a file belongs to another run or a task is in none of them.
+    pub fn merge_shards<P: AsRef<Path>>(&self, paths: &[P], answers: &AnswerSet, availables: &BTreeSet<WordId>, table: &PatternTable, config: &SearchConfig) -> io::Result<Best> {
+        self.check(answers, availables, table, config)?;
+
+        let mut results: BTreeMap<Task, Best> = BTreeMap::new();
+        for path in paths {
+            results.extend(Checkpoint::read(path, self.fingerprint)?);
+        }
+
+        let missing = self.tasks.iter().filter(|task| !results.contains_key(task)).count();
+        if missing > 0 {
+            return Err(invalid(format!("{} of {} tasks are missing from the shard files", missing, self.tasks.len())));
+        }
+
+        let searcher = Searcher::new(table, config.clone());
+        let plan = TaskPlan::new(&searcher, self.start_word, answers, availables);
+        Ok(plan.reduce(&searcher, results, availables))
+    }
+}
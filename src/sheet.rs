@@ -0,0 +1,170 @@
+use std::fmt::Write as _;
+
+use crate::common::{DecisionTree, Pattern};
+use crate::game::Checker;
+use crate::table::PatternTable;
+use crate::dictionary::WordId;
+use crate::answer_set::AnswerSet;
+use crate::utils::stat_color;
+use crate::export::pattern_label;
+
+// How cheat_sheet writes a tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SheetOptions {
+    // Markdown tables instead of plain text lines.
+    pub markdown: bool,
+    // Plain text colours the guesses like the Evaluator does, for terminals.
+    pub color: bool,
+    // Branches with fewer answers left are folded, into a <details> block in
+    // Markdown and a single line in plain text.
+    pub rare: usize,
+    // Answers listed per line before the rest are only counted, all when None.
+    pub list_limit: Option<usize>
+}
+
+impl Default for SheetOptions {
+    fn default() -> Self {
+        SheetOptions {
+            markdown: false,
+            color: false,
+            rare: 0,
+            list_limit: Some(12)
+        }
+    }
+}
+
+// A node of the tree: the moves that lead to it, its guess and the answers still possible.
+struct Line {
+    path: Vec<(WordId, Pattern)>,
+    guess: Option<WordId>,
+    answers: AnswerSet
+}
+
+fn collect(node: &DecisionTree, answers: &AnswerSet, table: &PatternTable, path: &mut Vec<(WordId, Pattern)>, lines: &mut Vec<Line>) {
+    lines.push(Line { path: path.clone(), guess: node.guess, answers: answers.clone() });
+
+    let Some(guess) = node.guess else {
+        return;
+    };
+    for (pattern, pattern_answers) in answers.partition(table.row(guess)) {
+        if Checker::is_success_pattern(pattern, table.word_len()) {
+            continue;
+        }
+        if let Some(subtree) = node.branch.get(&pattern) {
+            path.push((guess, pattern));
+            collect(subtree, &pattern_answers, table, path, lines);
+            path.pop();
+        }
+    }
+}
+
+struct Writer<'a> {
+    table: &'a PatternTable,
+    options: &'a SheetOptions,
+    sheet: String
+}
+
+impl Writer<'_> {
+    // One guess and the feedback it got, coloured by stat_color on terminals.
+    fn step(&self, guess: WordId, pattern: Pattern) -> String {
+        let word = self.table.dictionary().word(guess);
+        if self.options.color && !self.options.markdown {
+            stat_color(word, pattern)
+        } else {
+            format!("{} {}", word, pattern_label(pattern, self.table.word_len(), self.options.markdown))
+        }
+    }
+
+    fn path(&self, path: &[(WordId, Pattern)]) -> String {
+        let steps: Vec<_> = path.iter().map(|(guess, pattern)| self.step(*guess, *pattern)).collect();
+        steps.join(", ")
+    }
+
+    fn guess(&self, line: &Line) -> String {
+        line.guess.map_or("?".to_string(), |guess| self.table.dictionary().word(guess).to_string())
+    }
+
+    fn answers(&self, answers: &AnswerSet) -> String {
+        let limit = self.options.list_limit.unwrap_or(usize::MAX);
+        let listed: Vec<_> = answers.iter().take(limit).map(|answer| self.table.dictionary().word(answer)).collect();
+        match answers.len().saturating_sub(limit) {
+            0 => listed.join(", "),
+            more => format!("{} and {} more", listed.join(", "), more)
+        }
+    }
+
+    fn table(&mut self, lines: &[&Line]) {
+        self.sheet.push_str("| Path | Next guess | Left | Answers |\n|---|---|---|---|\n");
+        for line in lines {
+            let _ = writeln!(self.sheet, "| {} | **{}** | {} | {} |", self.path(&line.path), self.guess(line), line.answers.len(), self.answers(&line.answers));
+        }
+    }
+
+    // A group's lines, the first being the second guess after one pattern of the first.
+    fn group(&mut self, lines: &[Line]) {
+        let head = &lines[0];
+        let title = format!("{} → {} ({} left)", self.path(&head.path), self.guess(head), head.answers.len());
+        let rare = self.options.rare;
+        let (common, folded): (Vec<_>, Vec<_>) = lines.iter().partition(|line| line.answers.len() >= rare);
+
+        if self.options.markdown {
+            if head.answers.len() < rare {
+                let _ = writeln!(self.sheet, "<details><summary>{}</summary>\n", title);
+                self.table(&folded);
+                self.sheet.push_str("\n</details>\n\n");
+                return;
+            }
+
+            let _ = writeln!(self.sheet, "## {}\n", title);
+            self.table(&common);
+            if !folded.is_empty() {
+                let _ = writeln!(self.sheet, "\n<details><summary>{} rare branches</summary>\n", folded.len());
+                self.table(&folded);
+                self.sheet.push_str("\n</details>");
+            }
+            self.sheet.push('\n');
+            return;
+        }
+
+        if head.answers.len() < rare {
+            let _ = writeln!(self.sheet, "{}: {}", title, self.answers(&head.answers));
+            return;
+        }
+
+        let _ = writeln!(self.sheet, "{}", title);
+        for line in common.iter().skip(1) {
+            let _ = writeln!(self.sheet, "    {} -> {} ({}): {}", self.path(&line.path[1..]), self.guess(line), line.answers.len(), self.answers(&line.answers));
+        }
+        if !folded.is_empty() {
+            let answers: AnswerSet = folded.iter().flat_map(|line| line.answers.iter()).collect();
+            let _ = writeln!(self.sheet, "    + {} rare branches: {}", folded.len(), self.answers(&answers));
+        }
+        self.sheet.push('\n');
+    }
+}
+
+// The tree flattened into one line per guess after the first: the moves that
+// lead to it, the guess and the answers still possible. Lines are grouped by the
+// first two guesses, so a player finds their group from the first feedback.
+pub fn cheat_sheet(tree: &DecisionTree, answers: &AnswerSet, table: &PatternTable, options: &SheetOptions) -> String {
+    let mut lines = Vec::new();
+    collect(tree, answers, table, &mut Vec::new(), &mut lines);
+
+    let mut writer = Writer { table, options, sheet: String::new() };
+    let opener = lines[0].guess.map_or("?", |guess| table.dictionary().word(guess));
+    if options.markdown {
+        let _ = writeln!(writer.sheet, "# Open with {} ({} answers)\n", opener, answers.len());
+    } else {
+        let _ = writeln!(writer.sheet, "Open with {} ({} answers)\n", opener, answers.len());
+    }
+
+    // Lines come in preorder, each group starts at a line one move deep.
+    let mut start = 1;
+    while start < lines.len() {
+        let end = lines[start + 1..].iter().position(|line| line.path.len() == 1).map_or(lines.len(), |offset| start + 1 + offset);
+        writer.group(&lines[start..end]);
+        start = end;
+    }
+
+    writer.sheet
+}
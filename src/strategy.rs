@@ -0,0 +1,113 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::common::{Best, Pattern};
+use crate::config::SearchConfig;
+use crate::dfs::Searcher;
+use crate::game::Checker;
+use crate::table::PatternTable;
+use crate::dictionary::WordId;
+use crate::answer_set::AnswerSet;
+
+// Picks the next guess for a game in progress, given the answers still possible
+// and the guesses still allowed.
+pub trait Strategy {
+    fn name(&self) -> String;
+
+    // None when no allowed guess tells the answers apart.
+    fn suggest(&self, answers: &AnswerSet, availables: &BTreeSet<WordId>) -> Option<WordId>;
+}
+
+// Best guess by the configured heuristic, no search. Small sets only try their
+// own answers, like the search.
+pub struct Greedy<'a> {
+    searcher: Searcher<'a>,
+    // Rescore this many of the best guesses by also playing the best guess in
+    // each bucket they leave.
+    pub lookahead: Option<usize>
+}
+
+impl<'a> Greedy<'a> {
+    pub fn new(table: &'a PatternTable, config: SearchConfig, lookahead: Option<usize>) -> Self {
+        Greedy {
+            searcher: Searcher::new(table, config),
+            lookahead
+        }
+    }
+
+    fn candidates(&self, answers: &AnswerSet, availables: &BTreeSet<WordId>) -> BTreeSet<WordId> {
+        if answers.len() <= self.searcher.config.small_set_threshold {
+            answers.iter().filter(|answer| availables.contains(answer)).collect()
+        } else {
+            availables.clone()
+        }
+    }
+
+    // The guess's own score plus the prior weighted mean of the best score one
+    // more guess reaches in each bucket. A bucket nothing splits adds nothing.
+    fn two_ply(&self, guess: WordId, score: f64, groups: &BTreeMap<Pattern, AnswerSet>, availables: &BTreeSet<WordId>) -> f64 {
+        let word_len = self.searcher.table.word_len();
+        let mass: f64 = groups.values().map(|group| self.searcher.weight(group)).sum();
+
+        let next: f64 = groups.iter().filter(|(pattern, _)| !Checker::is_success_pattern(**pattern, word_len)).map(|(pattern, pattern_answers)| {
+            let next_availables = self.candidates(pattern_answers, &self.searcher.narrow(guess, *pattern, availables));
+            let best = self.searcher.rank(pattern_answers, &next_availables, 1).first().map_or(0.0, |(_, score, _)| *score);
+            best * self.searcher.weight(pattern_answers)
+        }).sum();
+
+        score + next / mass
+    }
+
+    // A whole tree, each set answered by suggest. Fast, and a baseline for the search.
+    pub fn tree(&self, answers: &AnswerSet, availables: &BTreeSet<WordId>) -> Best {
+        let searcher = &self.searcher;
+        let word_len = searcher.table.word_len();
+
+        if answers.len() == 1 {
+            return searcher.single(answers);
+        }
+
+        let Some(guess) = self.suggest(answers, availables) else {
+            return Best::new();
+        };
+
+        let mut current_guess = searcher.init(guess, answers);
+        for (pattern, pattern_answers) in answers.partition(searcher.table.row(guess)) {
+            let sub_result = if Checker::is_success_pattern(pattern, word_len) {
+                Best::success()
+            } else {
+                self.tree(&pattern_answers, &searcher.narrow(guess, pattern, availables))
+            };
+
+            if !sub_result.has_result {
+                return Best::new();
+            }
+            current_guess.update(pattern, sub_result);
+        }
+
+        current_guess.finish();
+        current_guess
+    }
+}
+
+impl Strategy for Greedy<'_> {
+    fn name(&self) -> String {
+        match self.lookahead {
+            Some(width) => format!("greedy:{}:{}", self.searcher.config.heuristic.name(), width),
+            None => format!("greedy:{}", self.searcher.config.heuristic.name())
+        }
+    }
+
+    fn suggest(&self, answers: &AnswerSet, availables: &BTreeSet<WordId>) -> Option<WordId> {
+        let candidates = self.candidates(answers, availables);
+
+        match self.lookahead {
+            None => self.searcher.rank(answers, &candidates, 1).first().map(|(guess, _, _)| *guess),
+            Some(width) => self.searcher
+                .rank(answers, &candidates, width)
+                .into_iter()
+                .map(|(guess, score, groups)| (guess, self.two_ply(guess, score, &groups, availables)))
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(guess, _)| guess)
+        }
+    }
+}
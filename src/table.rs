@@ -0,0 +1,140 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::common::Pattern;
+use crate::dictionary::{Dictionary, WordId};
+use crate::game::Checker;
+use crate::encoding::{read_u8, read_u32, read_u64};
+
+const MAGIC: &[u8; 4] = b"WPTB";
+const VERSION: u8 = 2;
+
+// Every guess checked against every answer once, so the search never calls Checker::check.
+// Row major: the pattern of guess g against answer a is at g * answer_count + a.
+pub struct PatternTable {
+    dictionary: Dictionary,
+    patterns: Vec<Pattern>
+}
+
+impl PatternTable {
+    pub fn new(dictionary: Dictionary) -> Self {
+        let answers: Vec<_> = dictionary.iter().take(dictionary.answer_count()).collect();
+        let patterns = dictionary.iter()
+            .collect::<Vec<_>>()
+            .par_iter()
+            .flat_map_iter(|guess| answers.iter().map(move |answer| Checker::check(answer, guess)))
+            .collect();
+
+        PatternTable {
+            dictionary,
+            patterns
+        }
+    }
+
+    pub fn dictionary(&self) -> &Dictionary {
+        &self.dictionary
+    }
+
+    pub fn word_len(&self) -> usize {
+        self.dictionary.word_len()
+    }
+
+    pub fn get(&self, guess: WordId, answer: WordId) -> Pattern {
+        debug_assert!(self.dictionary.is_answer(answer));
+        self.patterns[guess as usize * self.dictionary.answer_count() + answer as usize]
+    }
+
+    // Patterns of one guess against every answer, indexed by answer id.
+    pub fn row(&self, guess: WordId) -> &[Pattern] {
+        let width = self.dictionary.answer_count();
+        &self.patterns[guess as usize * width..(guess as usize + 1) * width]
+    }
+
+    fn pattern_bytes(word_len: usize) -> usize {
+        if Checker::pattern_count(word_len) <= 256 { 1 } else { 2 }
+    }
+
+    // Layout: magic, version, word length, guess count, answer count, checksum, then one pattern
+    // per cell, a single byte when every pattern fits (five letters or fewer) and u16 le otherwise.
+    pub fn save_to_path<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, self.word_len() as u8])?;
+        writer.write_all(&(self.dictionary.len() as u32).to_le_bytes())?;
+        writer.write_all(&(self.dictionary.answer_count() as u32).to_le_bytes())?;
+        writer.write_all(&self.dictionary.checksum().to_le_bytes())?;
+
+        if PatternTable::pattern_bytes(self.word_len()) == 1 {
+            let bytes: Vec<u8> = self.patterns.iter().map(|p| *p as u8).collect();
+            writer.write_all(&bytes)?;
+        } else {
+            let bytes: Vec<u8> = self.patterns.iter().flat_map(|p| p.to_le_bytes()).collect();
+            writer.write_all(&bytes)?;
+        }
+
+        writer.flush()
+    }
+
+    // Fails with InvalidData when the file was built from another dictionary.
+    pub fn load_from_path<P: AsRef<Path>>(path: P, dictionary: Dictionary) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        if &magic != MAGIC {
+            return Err(invalid("not a pattern table file"));
+        }
+        if read_u8(&mut reader)? != VERSION {
+            return Err(invalid("unsupported pattern table version"));
+        }
+
+        let word_len = read_u8(&mut reader)? as usize;
+        let guess_count = read_u32(&mut reader)? as usize;
+        let answer_count = read_u32(&mut reader)? as usize;
+        let checksum = read_u64(&mut reader)?;
+
+        if word_len != dictionary.word_len()
+            || guess_count != dictionary.len()
+            || answer_count != dictionary.answer_count()
+            || checksum != dictionary.checksum() {
+            return Err(invalid("pattern table was built from different word lists"));
+        }
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        let pattern_bytes = PatternTable::pattern_bytes(dictionary.word_len());
+        if bytes.len() != guess_count * answer_count * pattern_bytes {
+            return Err(invalid("pattern table is truncated"));
+        }
+
+        let patterns = if pattern_bytes == 1 {
+            bytes.into_iter().map(Pattern::from).collect()
+        } else {
+            bytes.chunks_exact(2).map(|b| Pattern::from_le_bytes([b[0], b[1]])).collect()
+        };
+
+        Ok(PatternTable {
+            dictionary,
+            patterns
+        })
+    }
+
+    // Reuse a table file when it matches the dictionary, otherwise build one and try to write it back.
+    pub fn load_or_build<P: AsRef<Path>>(path: P, dictionary: Dictionary) -> Self {
+        if let Ok(table) = PatternTable::load_from_path(&path, dictionary.clone()) {
+            return table;
+        }
+
+        let table = PatternTable::new(dictionary);
+        let _ = table.save_to_path(&path);
+        table
+    }
+}
@@ -0,0 +1,456 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem::size_of;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::common::{Best, DecisionTree, Pattern};
+use crate::answer_set::AnswerSet;
+use crate::config::SearchConfig;
+use crate::dictionary::{Dictionary, WordId};
+use crate::encoding::{StableHasher, read_u8, read_u16, read_u32, read_u64};
+
+const MAGIC: &[u8; 4] = b"WSTT";
+const VERSION: u8 = 2;
+
+// A search state reduced to two independent 64 bit hashes, `hash` picks the
+// slot and `check` tells apart the rare states that share it. The answers left
+// and the guesses still allowed are the whole state: how a search got there,
+// hard mode restrictions included, only matters through them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StateKey {
+    pub hash: u64,
+    pub check: u64
+}
+
+impl StateKey {
+    pub fn new(answers: &AnswerSet, availables: &BTreeSet<WordId>) -> Self {
+        let hash_with = |salt: u64| {
+            let mut hasher = StableHasher::new(salt);
+            answers.iter().for_each(|id| hasher.add(id as u64));
+            hasher.add(u64::MAX);
+            availables.iter().for_each(|id| hasher.add(*id as u64));
+            hasher.0
+        };
+
+        StateKey {
+            hash: hash_with(0),
+            check: hash_with(1)
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TableStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64
+}
+
+impl TableStats {
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+// What the table knows for a state searched with some turns remaining.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lookup {
+    // The result of a search with exactly these turns, true when a beam cut
+    // guesses below it.
+    Result(Best, bool),
+    // A search with at least these turns and no guess cut found nothing.
+    NoResult,
+    // A result with fewer turns, or one a beam of another shape cut guesses
+    // from. Still a valid tree to beat.
+    Baseline(Best),
+    Miss
+}
+
+#[derive(Debug, Clone)]
+struct Level {
+    best: Best,
+    // SearchConfig::shape of the search when its beam cut guesses, None when
+    // every guess was tried and the result is final for any beam.
+    shape: Option<u64>
+}
+
+#[derive(Debug)]
+struct Entry {
+    check: u64,
+    // Results by the turns remaining when they were searched.
+    levels: BTreeMap<u8, Level>,
+    // Most remaining turns a search with no guess cut failed with, 0 when none did.
+    no_result_within: u8,
+    // Search nodes spent on the results, cheap entries are evicted first.
+    cost: u64,
+    bytes: usize
+}
+
+// Rough heap and inline size of a cached result.
+fn best_bytes(best: &Best) -> usize {
+    size_of::<Best>() + best.decision_tree.node_count() * size_of::<(Pattern, DecisionTree)>()
+}
+
+fn write_tree<W: Write>(writer: &mut W, tree: &DecisionTree) -> io::Result<()> {
+    writer.write_all(&tree.guess.unwrap_or(WordId::MAX).to_le_bytes())?;
+    writer.write_all(&(tree.branch.len() as u16).to_le_bytes())?;
+    for (pattern, subtree) in tree.branch.iter() {
+        writer.write_all(&pattern.to_le_bytes())?;
+        write_tree(writer, subtree)?;
+    }
+    Ok(())
+}
+
+fn read_tree<R: Read>(reader: &mut R) -> io::Result<DecisionTree> {
+    let guess = read_u16(reader)?;
+    let mut tree = DecisionTree::new();
+    tree.guess = (guess != WordId::MAX).then_some(guess);
+    for _ in 0..read_u16(reader)? {
+        let pattern = read_u16(reader)?;
+        tree.add_branch(pattern, read_tree(reader)?);
+    }
+    Ok(tree)
+}
+
+// A found result: max_level, total_count, depth_counts, weighted_count and the tree.
+pub fn write_best<W: Write>(writer: &mut W, best: &Best) -> io::Result<()> {
+    writer.write_all(&[best.max_level])?;
+    writer.write_all(&best.total_count.to_le_bytes())?;
+    for count in best.depth_counts.iter() {
+        writer.write_all(&count.to_le_bytes())?;
+    }
+    writer.write_all(&best.weighted_count.to_le_bytes())?;
+    write_tree(writer, &best.decision_tree)
+}
+
+pub fn read_best<R: Read>(reader: &mut R) -> io::Result<Best> {
+    let mut best = Best::empty();
+    best.max_level = read_u8(reader)?;
+    best.total_count = read_u32(reader)?;
+    for count in best.depth_counts.iter_mut() {
+        *count = read_u32(reader)?;
+    }
+    best.weighted_count = f64::from_bits(read_u64(reader)?);
+    best.decision_tree = read_tree(reader)?;
+    Ok(best)
+}
+
+fn save_entries<'a, P: AsRef<Path>>(path: P, fingerprint: u64, count: usize, entries: impl Iterator<Item = (&'a u64, &'a Entry)>) -> io::Result<()> {
+    // Written next to the target and renamed, an interrupted save keeps the old file.
+    let partial = path.as_ref().with_extension("partial");
+    let mut writer = BufWriter::new(File::create(&partial)?);
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION])?;
+    writer.write_all(&fingerprint.to_le_bytes())?;
+    writer.write_all(&(count as u64).to_le_bytes())?;
+
+    for (hash, entry) in entries {
+        writer.write_all(&hash.to_le_bytes())?;
+        writer.write_all(&entry.check.to_le_bytes())?;
+        writer.write_all(&[entry.no_result_within])?;
+        writer.write_all(&entry.cost.to_le_bytes())?;
+        writer.write_all(&[entry.levels.len() as u8])?;
+
+        for (remaining, level) in entry.levels.iter() {
+            writer.write_all(&[*remaining])?;
+            writer.write_all(&[level.shape.is_some() as u8])?;
+            if let Some(shape) = level.shape {
+                writer.write_all(&shape.to_le_bytes())?;
+            }
+            write_best(&mut writer, &level.best)?;
+        }
+    }
+
+    writer.flush()?;
+    drop(writer);
+    fs::rename(partial, path)
+}
+
+// Cached search results by state, holding at most `capacity` bytes when set.
+// Going over the cap evicts the cheapest entries down to three quarters of it.
+#[derive(Debug, Default)]
+pub struct TranspositionTable {
+    entries: HashMap<u64, Entry>,
+    capacity: Option<usize>,
+    bytes: usize,
+    pub stats: TableStats
+}
+
+impl TranspositionTable {
+    pub fn new(capacity: Option<usize>) -> Self {
+        TranspositionTable {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Estimated memory held by the entries.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    // For a search of SearchConfig::shape `shape`. Fewer turns never help, so a
+    // failure also covers every smaller budget and a result found with fewer turns
+    // bounds this one. A result another beam cut guesses from only bounds it too,
+    // a wider beam may do better. Baselines count as misses, the state is still searched.
+    pub fn lookup(&mut self, key: &StateKey, remaining: u8, shape: u64) -> Lookup {
+        let lookup = match self.entries.get(&key.hash) {
+            Some(entry) if entry.check == key.check => {
+                match entry.levels.get(&remaining) {
+                    Some(level) if level.shape.is_none_or(|cut| cut == shape) => Lookup::Result(level.best.clone(), level.shape.is_some()),
+                    _ if remaining <= entry.no_result_within => Lookup::NoResult,
+                    _ => match entry.levels.range(..=remaining).next_back() {
+                        Some((_, level)) => Lookup::Baseline(level.best.clone()),
+                        None => Lookup::Miss
+                    }
+                }
+            },
+            _ => Lookup::Miss
+        };
+
+        match lookup {
+            Lookup::Result(..) | Lookup::NoResult => self.stats.hits += 1,
+            Lookup::Baseline(_) | Lookup::Miss => self.stats.misses += 1
+        }
+        lookup
+    }
+
+    // Records what a search with `remaining` turns found, `cost` is the nodes it took
+    // and `shape` the SearchConfig::shape of its beam when that cut guesses. Only a
+    // failure with no guess cut proves there is no tree.
+    pub fn insert(&mut self, key: StateKey, remaining: u8, best: Best, cost: u64, shape: Option<u64>) {
+        // A colliding state takes the slot over.
+        if self.entries.get(&key.hash).is_some_and(|entry| entry.check != key.check) {
+            let old = self.entries.remove(&key.hash).unwrap();
+            self.bytes -= old.bytes;
+        }
+
+        let before = self.entries.get(&key.hash).map_or(0, |entry| entry.bytes);
+        let entry = self.entries.entry(key.hash).or_insert_with(|| Entry {
+            check: key.check,
+            levels: BTreeMap::new(),
+            no_result_within: 0,
+            cost: 0,
+            bytes: size_of::<(u64, Entry)>()
+        });
+
+        // A result no beam cut stays, it is final for every search.
+        let keep = entry.levels.get(&remaining).is_some_and(|old| old.shape.is_none() && shape.is_some());
+        if best.has_result && !keep {
+            entry.bytes += best_bytes(&best);
+            if let Some(old) = entry.levels.insert(remaining, Level { best, shape }) {
+                entry.bytes -= best_bytes(&old.best);
+            }
+        } else if !best.has_result && shape.is_none() {
+            entry.no_result_within = entry.no_result_within.max(remaining);
+        }
+        entry.cost += cost;
+        self.bytes = self.bytes - before + entry.bytes;
+
+        if self.capacity.is_some_and(|capacity| self.bytes > capacity) {
+            self.evict();
+        }
+    }
+
+    // What the cached results depend on: the word lists, the rules, the priors,
+    // what counts as better and below which size sets are solved without search.
+    // Runs with other beams and heuristics share entries, but what a beam cut
+    // guesses from is tagged with its shape and only a baseline for the others.
+    pub fn fingerprint(dictionary: &Dictionary, config: &SearchConfig) -> u64 {
+        let mut hasher = StableHasher::new(0);
+        hasher.add(dictionary.checksum());
+        hasher.add(config.max_turns as u64);
+        hasher.add_bytes(config.objective.name().as_bytes());
+        hasher.add(config.hard_mode as u64);
+        hasher.add(config.exact as u64);
+        hasher.add(config.small_set_threshold as u64);
+        // Uniform caches keep the fingerprint they had before priors existed.
+        if let Some(priors) = &config.priors {
+            hasher.add(priors.fingerprint());
+        }
+        hasher.0
+    }
+
+    // Layout: magic, version, fingerprint, entry count, then per entry its hash, check,
+    // no_result_within, cost and results. A result is its remaining turns, a byte telling
+    // whether a beam cut it followed by that beam's shape, max_level, total_count,
+    // depth_counts, weighted_count and the tree in preorder, each node a
+    // guess (u16::MAX for none) and its branch count followed by pattern, subtree pairs.
+    pub fn save_to_path<P: AsRef<Path>>(&self, path: P, fingerprint: u64) -> io::Result<()> {
+        save_entries(path, fingerprint, self.entries.len(), self.entries.iter())
+    }
+
+    // Fails with InvalidData when the file was written for other word lists or settings.
+    pub fn load_from_path<P: AsRef<Path>>(path: P, fingerprint: u64, capacity: Option<usize>) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a solver cache file"));
+        }
+        if read_u8(&mut reader)? != VERSION {
+            return Err(invalid("unsupported solver cache version"));
+        }
+        if read_u64(&mut reader)? != fingerprint {
+            return Err(invalid("solver cache was written for different word lists or settings"));
+        }
+
+        let mut table = TranspositionTable::new(capacity);
+        for _ in 0..read_u64(&mut reader)? {
+            let hash = read_u64(&mut reader)?;
+            let mut entry = Entry {
+                check: read_u64(&mut reader)?,
+                levels: BTreeMap::new(),
+                no_result_within: read_u8(&mut reader)?,
+                cost: read_u64(&mut reader)?,
+                bytes: size_of::<(u64, Entry)>()
+            };
+
+            for _ in 0..read_u8(&mut reader)? {
+                let remaining = read_u8(&mut reader)?;
+                let shape = match read_u8(&mut reader)? {
+                    0 => None,
+                    _ => Some(read_u64(&mut reader)?)
+                };
+                let best = read_best(&mut reader)?;
+
+                entry.bytes += best_bytes(&best);
+                entry.levels.insert(remaining, Level { best, shape });
+            }
+
+            table.bytes += entry.bytes;
+            table.entries.insert(hash, entry);
+        }
+
+        if read_u8(&mut reader).is_ok() {
+            return Err(invalid("solver cache has trailing data"));
+        }
+
+        if table.capacity.is_some_and(|capacity| table.bytes > capacity) {
+            table.evict();
+        }
+        Ok(table)
+    }
+
+    // Starts from the file when it matches, otherwise from an empty table.
+    pub fn load_or_new<P: AsRef<Path>>(path: P, fingerprint: u64, capacity: Option<usize>) -> Self {
+        TranspositionTable::load_from_path(path, fingerprint, capacity).unwrap_or_else(|_| TranspositionTable::new(capacity))
+    }
+
+    fn evict(&mut self) {
+        let target = self.capacity.unwrap_or(usize::MAX) / 4 * 3;
+
+        let mut by_cost: Vec<_> = self.entries.iter().map(|(hash, entry)| (entry.cost, *hash)).collect();
+        by_cost.sort_unstable();
+
+        for (_, hash) in by_cost {
+            if self.bytes <= target {
+                break;
+            }
+            let entry = self.entries.remove(&hash).unwrap();
+            self.bytes -= entry.bytes;
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+const SHARDS: usize = 16;
+
+// TranspositionTable split by key so parallel searches rarely wait on each other,
+// each shard holds its part of the memory cap. Saves the same file format.
+#[derive(Debug)]
+pub struct ShardedTable {
+    shards: Vec<Mutex<TranspositionTable>>
+}
+
+impl ShardedTable {
+    pub fn new(capacity: Option<usize>) -> Self {
+        ShardedTable {
+            shards: (0..SHARDS).map(|_| Mutex::new(TranspositionTable::new(capacity.map(|capacity| capacity / SHARDS)))).collect()
+        }
+    }
+
+    fn shard(&self, key: &StateKey) -> MutexGuard<'_, TranspositionTable> {
+        self.shards[key.hash as usize % SHARDS].lock().unwrap()
+    }
+
+    fn locked(&self) -> Vec<MutexGuard<'_, TranspositionTable>> {
+        self.shards.iter().map(|shard| shard.lock().unwrap()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.locked().iter().map(|shard| shard.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locked().iter().all(|shard| shard.is_empty())
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.locked().iter().map(|shard| shard.bytes()).sum()
+    }
+
+    // Summed over the shards.
+    pub fn stats(&self) -> TableStats {
+        self.locked().iter().fold(TableStats::default(), |total, shard| TableStats {
+            hits: total.hits + shard.stats.hits,
+            misses: total.misses + shard.stats.misses,
+            evictions: total.evictions + shard.stats.evictions
+        })
+    }
+
+    pub fn lookup(&self, key: &StateKey, remaining: u8, shape: u64) -> Lookup {
+        self.shard(key).lookup(key, remaining, shape)
+    }
+
+    pub fn insert(&self, key: StateKey, remaining: u8, best: Best, cost: u64, shape: Option<u64>) {
+        self.shard(&key).insert(key, remaining, best, cost, shape)
+    }
+
+    pub fn save_to_path<P: AsRef<Path>>(&self, path: P, fingerprint: u64) -> io::Result<()> {
+        let shards = self.locked();
+        let count = shards.iter().map(|shard| shard.len()).sum();
+        save_entries(path, fingerprint, count, shards.iter().flat_map(|shard| shard.entries.iter()))
+    }
+
+    pub fn load_from_path<P: AsRef<Path>>(path: P, fingerprint: u64, capacity: Option<usize>) -> io::Result<Self> {
+        let loaded = TranspositionTable::load_from_path(path, fingerprint, None)?;
+        let table = ShardedTable::new(capacity);
+
+        for (hash, entry) in loaded.entries {
+            let mut shard = table.shards[hash as usize % SHARDS].lock().unwrap();
+            shard.bytes += entry.bytes;
+            shard.entries.insert(hash, entry);
+        }
+        for shard in table.shards.iter() {
+            let mut shard = shard.lock().unwrap();
+            if shard.capacity.is_some_and(|capacity| shard.bytes > capacity) {
+                shard.evict();
+            }
+        }
+        Ok(table)
+    }
+
+    pub fn load_or_new<P: AsRef<Path>>(path: P, fingerprint: u64, capacity: Option<usize>) -> Self {
+        ShardedTable::load_from_path(path, fingerprint, capacity).unwrap_or_else(|_| ShardedTable::new(capacity))
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+
+use crate::common::{DecisionTree, Pattern};
+use crate::game::Checker;
+use crate::dictionary::{Dictionary, WordId};
+use crate::encoding::{read_u8, read_u16, read_u64};
+
+const MAGIC: &[u8; 4] = b"WDTB";
+const VERSION: u8 = 1;
+
+// Marks a node with a leaf behind its success pattern, in its edge count.
+const SUCCESS_LEAF: u16 = 1 << 15;
+// Deeper than any tree a search builds, so a corrupt file cannot exhaust the stack.
+const MAX_DEPTH: usize = u8::MAX as usize;
+
+// Whether `bytes` start like write_tree_file output rather than JSON.
+pub fn is_tree_file(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+fn wide_patterns(word_len: usize) -> bool {
+    Checker::pattern_count(word_len) > 256
+}
+
+fn is_leaf(tree: &DecisionTree) -> bool {
+    tree.guess.is_none() && tree.branch.is_empty()
+}
+
+fn write_node<W: Write>(writer: &mut W, tree: &DecisionTree, word_len: usize) -> io::Result<()> {
+    let success = Checker::success_pattern(word_len);
+    let success_leaf = tree.branch.get(&success).is_some_and(is_leaf);
+    let edges: Vec<_> = tree.branch.iter().filter(|(pattern, _)| !(success_leaf && **pattern == success)).collect();
+
+    writer.write_all(&tree.guess.unwrap_or(u16::MAX).to_le_bytes())?;
+    writer.write_all(&(edges.len() as u16 | if success_leaf { SUCCESS_LEAF } else { 0 }).to_le_bytes())?;
+
+    for (pattern, subtree) in edges {
+        if wide_patterns(word_len) {
+            writer.write_all(&pattern.to_le_bytes())?;
+        } else {
+            writer.write_all(&[*pattern as u8])?;
+        }
+        write_node(writer, subtree, word_len)?;
+    }
+    Ok(())
+}
+
+fn read_node<R: Read>(reader: &mut R, dictionary: &Dictionary, depth: usize) -> io::Result<DecisionTree> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let word_len = dictionary.word_len();
+
+    if depth > MAX_DEPTH {
+        return Err(invalid("decision tree is nested too deep".to_string()));
+    }
+
+    let guess = match read_u16(reader)? {
+        u16::MAX => None,
+        id if (id as usize) < dictionary.len() => Some(id as WordId),
+        id => return Err(invalid(format!("decision tree has word id {} beyond the {} words", id, dictionary.len())))
+    };
+    let count = read_u16(reader)?;
+
+    let mut tree = DecisionTree { guess, branch: BTreeMap::new() };
+    if count & SUCCESS_LEAF != 0 {
+        tree.add_branch(Checker::success_pattern(word_len), DecisionTree::new());
+    }
+
+    for _ in 0..count & !SUCCESS_LEAF {
+        let pattern: Pattern = if wide_patterns(word_len) { read_u16(reader)? } else { read_u8(reader)? as Pattern };
+        if pattern as usize >= Checker::pattern_count(word_len) {
+            return Err(invalid(format!("decision tree has pattern {} for {} letter words", pattern, word_len)));
+        }
+        tree.add_branch(pattern, read_node(reader, dictionary, depth + 1)?);
+    }
+    Ok(tree)
+}
+
+// A decision tree for shipping, an order of magnitude smaller than its JSON.
+//
+// Layout: magic, version, word length, the dictionary checksum, then the nodes in
+// preorder. A node is its guess (u16::MAX for none) and its edge count, with the
+// top bit set when the guess's success pattern leads to a leaf. That leaf is not
+// written, every other edge is its pattern followed by the subtree. Patterns take
+// a byte when every pattern fits (five letters or fewer) and u16 le otherwise.
+pub fn write_tree_file<W: Write>(writer: &mut W, tree: &DecisionTree, dictionary: &Dictionary) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION, dictionary.word_len() as u8])?;
+    writer.write_all(&dictionary.checksum().to_le_bytes())?;
+    write_node(writer, tree, dictionary.word_len())
+}
+
+// Fails with InvalidData when the file was written for other word lists.
+pub fn read_tree_file<R: Read>(reader: &mut R, dictionary: &Dictionary) -> io::Result<DecisionTree> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a decision tree file"));
+    }
+    if read_u8(reader)? != VERSION {
+        return Err(invalid("unsupported decision tree version"));
+    }
+    if read_u8(reader)? as usize != dictionary.word_len() || read_u64(reader)? != dictionary.checksum() {
+        return Err(invalid("decision tree was written for different word lists"));
+    }
+
+    let tree = read_node(reader, dictionary, 0)?;
+
+    if reader.read(&mut [0u8; 1])? != 0 {
+        return Err(invalid("decision tree file has trailing bytes"));
+    }
+    Ok(tree)
+}
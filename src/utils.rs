@@ -1,68 +1,210 @@
-use std::collections::{BTreeMap, BTreeSet};
-use crate::common::Restriction;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use crate::common::{Restriction, Pattern, WordListError, MIN_WORD_LEN, MAX_WORD_LEN};
 use crate::game::Checker;
+use crate::table::PatternTable;
+use crate::dictionary::{Dictionary, WordId};
+use crate::answer_set::AnswerSet;
+use crate::heuristic::GuessHeuristic;
+use crate::prior::Priors;
+use crate::encoding::{FNV_OFFSET, fnv1a};
 
-pub fn group_by_pattern<'a>(guess: &'a str, answers: &BTreeSet<&'a str>) -> BTreeMap<u8, BTreeSet<&'a str>>{
-    let mut groups = BTreeMap::new();
-    for answer in answers.into_iter() {
-        let pattern = Checker::check(answer, guess);
-        (*groups.entry(pattern).or_insert_with(BTreeSet::new)).insert(answer.clone());
+// Parse a newline separated word list, all words must share one supported length.
+pub fn load_word_list(content: &str) -> Result<BTreeSet<&str>, WordListError> {
+    let mut words = BTreeSet::new();
+    let mut expected = None;
+
+    for word in content.lines().map(|line| line.trim()).filter(|line| !line.is_empty()) {
+        let length = word.chars().count();
+        match expected {
+            None => {
+                if !(MIN_WORD_LEN..=MAX_WORD_LEN).contains(&length) {
+                    return Err(WordListError::UnsupportedLength { word: word.to_string(), length });
+                }
+                expected = Some(length);
+            },
+            Some(expected) if expected != length => {
+                return Err(WordListError::MixedLengths { expected, word: word.to_string() });
+            },
+            _ => ()
+        }
+        words.insert(word);
+    }
+
+    if words.is_empty() {
+        return Err(WordListError::Empty);
     }
-    groups
+
+    Ok(words)
+}
+
+// Load answers and guesses together, rejecting lists of different lengths.
+pub fn load_word_lists<'a>(answers: &'a str, words: &'a str) -> Result<(BTreeSet<&'a str>, BTreeSet<&'a str>), WordListError> {
+    let answers = load_word_list(answers)?;
+    let words = load_word_list(words)?;
+
+    let expected = word_length(&answers);
+    if let Some(word) = words.iter().find(|word| word.chars().count() != expected) {
+        return Err(WordListError::MixedLengths { expected, word: word.to_string() });
+    }
+
+    Ok((answers, words))
+}
+
+pub fn word_length(words: &BTreeSet<&str>) -> usize {
+    words.iter().next().map_or(0, |word| word.chars().count())
+}
+
+pub fn group_by_pattern(table: &PatternTable, guess: WordId, answers: &AnswerSet) -> BTreeMap<Pattern, AnswerSet>{
+    answers.partition(table.row(guess))
 }
 
 
-pub fn get_entropy(pattern: u8, length: u32) -> u32 {
+pub fn get_entropy(pattern: Pattern, word_len: usize, length: u32) -> u32 {
 
-    if pattern == 242 {
-        return 0
+    if Checker::is_success_pattern(pattern, word_len) {
+        0
     } else {
-        return 2 * length - 1;
+        2 * length - 1
     }
-    
+
 }
 
-pub fn get_lower_bound_level(length: usize) -> u8 {
+pub fn get_lower_bound_level(length: usize, word_len: usize) -> u8 {
 
     match length {
         1 => 1,
-        2..=243 => 2,
+        n if n <= Checker::pattern_count(word_len) => 2,
         _ => 3
     }
 }
 
-pub fn get_entropy_sum<'a>(guess: &'a str, answers: &BTreeSet<&'a str>) -> (&'a str, u32, BTreeMap<u8, BTreeSet<&'a str>>) {
-    let groups = group_by_pattern(guess, answers);
+pub fn get_entropy_sum(table: &PatternTable, guess: WordId, answers: &AnswerSet) -> (WordId, u32, BTreeMap<Pattern, AnswerSet>) {
+    let groups = group_by_pattern(table, guess, answers);
 
     let entropy = groups.iter().map(|(pattern, group)| {
-        get_entropy(*pattern, group.len() as u32)
+        get_entropy(*pattern, table.word_len(), group.len() as u32)
     }).sum();
 
     (guess, entropy, groups)
 }
 
-pub fn filter_available_guesses<'a> (restriction: &Restriction, words: &BTreeSet<&'a str>) -> BTreeSet<&'a str> {
-    words.iter().filter(|word| {
-        restriction.evaluate(word)
-    }).cloned().collect()
+// Bucket sizes and prior masses of one guess, reused across guesses so ranking
+// does not allocate per guess.
+pub struct PatternCounter {
+    counts: Vec<u32>,
+    masses: Vec<f64>,
+    touched: Vec<Pattern>
 }
 
-pub fn filter_available_answers<'a> (guess: &'a str, pattern: u8, answers: &BTreeSet<&'a str>) -> BTreeSet<&'a str> {
-    answers.iter().filter(|answer| {
-        Checker::check(answer, guess) == pattern
+impl PatternCounter {
+    pub fn new(word_len: usize) -> Self {
+        PatternCounter {
+            counts: vec![0; Checker::pattern_count(word_len)],
+            masses: vec![0.0; Checker::pattern_count(word_len)],
+            touched: Vec::new()
+        }
+    }
+
+    // Count the answers per pattern and sum their weights, every answer weighs 1
+    // without priors. Returns a fingerprint of the partition (patterns in answer order).
+    pub fn count(&mut self, row: &[Pattern], answers: &[WordId], priors: Option<&Priors>) -> u64 {
+        self.clear();
+
+        let mut fingerprint = FNV_OFFSET;
+        for answer in answers {
+            let pattern = row[*answer as usize];
+            let count = &mut self.counts[pattern as usize];
+            if *count == 0 {
+                self.touched.push(pattern);
+            }
+            *count += 1;
+            self.masses[pattern as usize] += priors.map_or(1.0, |priors| priors.weight(*answer));
+
+            fingerprint = fnv1a(fingerprint, pattern as u64);
+        }
+        fingerprint
+    }
+
+    // A single bucket that is not the success pattern.
+    pub fn splits_nothing(&self, word_len: usize) -> bool {
+        self.touched.len() == 1 && !Checker::is_success_pattern(self.touched[0], word_len)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Pattern, u32)> + '_ {
+        self.touched.iter().map(|pattern| (*pattern, self.counts[*pattern as usize]))
+    }
+
+    // Like iter with the summed prior weight of each bucket.
+    pub fn weighted(&self) -> impl Iterator<Item = (Pattern, u32, f64)> + '_ {
+        self.touched.iter().map(|pattern| (*pattern, self.counts[*pattern as usize], self.masses[*pattern as usize]))
+    }
+
+    pub fn clear(&mut self) {
+        for pattern in self.touched.drain(..) {
+            self.counts[pattern as usize] = 0;
+            self.masses[pattern as usize] = 0.0;
+        }
+    }
+}
+
+// Guesses by `heuristic`, best first, with their partitions. Only the partitions
+// of the first `limit` distinct guesses are built. Guesses that leave every
+// answer in one bucket only waste a turn and are dropped. Without `dedup`
+// guesses with the same partition are all kept, hard mode narrows them apart.
+// Ties keep the order of `availables`. Buckets weigh their answers by `priors`.
+pub fn rank_guesses(table: &PatternTable, answers: &AnswerSet, availables: &BTreeSet<WordId>, limit: usize, dedup: bool, heuristic: &dyn GuessHeuristic, priors: Option<&Priors>) -> Vec<(WordId, f64, BTreeMap<Pattern, AnswerSet>)> {
+    let word_len = table.word_len();
+    let answer_ids: Vec<WordId> = answers.iter().collect();
+    let mut counter = PatternCounter::new(word_len);
+
+    let mut scored: Vec<_> = availables.iter().filter_map(|guess| {
+        let fingerprint = counter.count(table.row(*guess), &answer_ids, priors);
+        if counter.splits_nothing(word_len) {
+            return None;
+        }
+
+        Some((*guess, heuristic.score(&counter, word_len), fingerprint))
+    }).collect();
+
+    scored.sort_by(|(_, a, _), (_, b, _)| a.total_cmp(b));
+
+    let same_partition = |a: WordId, b: WordId| {
+        let (row_a, row_b) = (table.row(a), table.row(b));
+        answer_ids.iter().all(|answer| row_a[*answer as usize] == row_b[*answer as usize])
+    };
+
+    let mut seen: HashMap<u64, Vec<WordId>> = HashMap::new();
+    let mut top_guesses = Vec::new();
+    for (guess, score, fingerprint) in scored {
+        if top_guesses.len() >= limit {
+            break;
+        }
+
+        if dedup {
+            let representatives = seen.entry(fingerprint).or_default();
+            if representatives.iter().any(|other| same_partition(*other, guess)) {
+                continue;
+            }
+            representatives.push(guess);
+        }
+
+        top_guesses.push((guess, score, group_by_pattern(table, guess, answers)));
+    }
+
+    top_guesses
+}
+
+pub fn filter_available_guesses(dictionary: &Dictionary, restriction: &Restriction, words: &BTreeSet<WordId>) -> BTreeSet<WordId> {
+    words.iter().filter(|word| {
+        restriction.evaluate(dictionary.word(**word))
     }).cloned().collect()
 }
 
-// limit 15 can get best results.
-pub fn limit(_length: usize) -> usize {
-    13
-    // match length {
-    //     0..=15 => length,
-    //     _ => 15,
-    // }
+pub fn filter_available_answers(table: &PatternTable, guess: WordId, pattern: Pattern, answers: &AnswerSet) -> AnswerSet {
+    answers.filter(table.row(guess), pattern)
 }
 
-pub fn stat_color(guess: &str, stat: u8) -> String {
+pub fn stat_color(guess: &str, stat: Pattern) -> String {
     let mut s = String::new();
     let mut stat = stat;
     for c in guess.chars() {
@@ -77,4 +219,4 @@ pub fn stat_color(guess: &str, stat: u8) -> String {
     }
     s += "\x1b[0m";
     s
-}
\ No newline at end of file
+}
@@ -0,0 +1,183 @@
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+
+use crate::common::{DecisionTree, Pattern, Restriction};
+use crate::game::Checker;
+use crate::table::PatternTable;
+use crate::dictionary::{Dictionary, WordId};
+use crate::answer_set::AnswerSet;
+use crate::utils::filter_available_guesses;
+
+// What is wrong at one node of a tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    // Answers that get to the node but find no guess there, or no branch for the
+    // pattern they show.
+    Unsolved { answers: Vec<WordId> },
+    // A guess the rules do not allow after the guesses above it.
+    IllegalGuess { guess: WordId },
+    // A branch for a pattern no answer left can show.
+    ImpossiblePattern { pattern: Pattern },
+    // An answer found with more guesses than the cap.
+    TooDeep { answer: WordId, turns: u32 }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    // Guesses and patterns from the root to the node.
+    pub path: Vec<(WordId, Pattern)>,
+    pub problem: Problem
+}
+
+// Every problem of a tree, in preorder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub answer_count: usize,
+    // Answers that reach their success pattern, however deep.
+    pub solved: usize,
+    pub issues: Vec<Issue>
+}
+
+// The JSON form, words and patterns spelled out. Paths are moves like
+// "salet:00120", as the suggest and replay commands take them.
+#[derive(Serialize)]
+struct ReportFile<'a> {
+    valid: bool,
+    answers: usize,
+    solved: usize,
+    issues: Vec<IssueFile<'a>>
+}
+
+#[derive(Serialize)]
+struct IssueFile<'a> {
+    path: Vec<String>,
+    #[serde(flatten)]
+    problem: ProblemFile<'a>
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ProblemFile<'a> {
+    Unsolved { answers: Vec<&'a str> },
+    IllegalGuess { guess: &'a str },
+    ImpossiblePattern { pattern: String },
+    TooDeep { answer: &'a str, turns: u32 }
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    // Whether Evaluator::evaluate gets through the tree: every answer reaches a
+    // leaf by legal guesses, however deep.
+    pub fn is_playable(&self) -> bool {
+        self.issues.iter().all(|issue| matches!(issue.problem, Problem::ImpossiblePattern { .. } | Problem::TooDeep { .. }))
+    }
+
+    pub fn to_json(&self, dictionary: &Dictionary) -> serde_json::Result<String> {
+        let word_len = dictionary.word_len();
+        let file = ReportFile {
+            valid: self.is_valid(),
+            answers: self.answer_count,
+            solved: self.solved,
+            issues: self.issues.iter().map(|issue| IssueFile {
+                path: issue.path.iter().map(|(guess, pattern)| {
+                    format!("{}:{}", dictionary.word(*guess), Checker::format_pattern(*pattern, word_len))
+                }).collect(),
+                problem: match &issue.problem {
+                    Problem::Unsolved { answers } => ProblemFile::Unsolved { answers: dictionary.words(answers) },
+                    Problem::IllegalGuess { guess } => ProblemFile::IllegalGuess { guess: dictionary.word(*guess) },
+                    Problem::ImpossiblePattern { pattern } => ProblemFile::ImpossiblePattern { pattern: Checker::format_pattern(*pattern, word_len) },
+                    Problem::TooDeep { answer, turns } => ProblemFile::TooDeep { answer: dictionary.word(*answer), turns: *turns }
+                }
+            }).collect()
+        };
+        serde_json::to_string(&file)
+    }
+}
+
+struct Walk<'a> {
+    table: &'a PatternTable,
+    hard_mode: bool,
+    turn_cap: u32,
+    path: Vec<(WordId, Pattern)>,
+    report: ValidationReport
+}
+
+impl Walk<'_> {
+    fn issue(&mut self, step: Option<(WordId, Pattern)>, problem: Problem) {
+        let mut path = self.path.clone();
+        path.extend(step);
+        self.report.issues.push(Issue { path, problem });
+    }
+
+    // `answers` reach `node` with `allowed` left to guess.
+    fn visit(&mut self, node: &DecisionTree, answers: &AnswerSet, allowed: &BTreeSet<WordId>) {
+        let word_len = self.table.word_len();
+        let dictionary = self.table.dictionary();
+
+        let Some(guess) = node.guess else {
+            if !answers.is_empty() {
+                self.issue(None, Problem::Unsolved { answers: answers.iter().collect() });
+            }
+            return;
+        };
+
+        if !allowed.contains(&guess) {
+            self.issue(None, Problem::IllegalGuess { guess });
+        }
+
+        let groups = answers.partition(self.table.row(guess));
+        for pattern in node.branch.keys().filter(|pattern| !groups.contains_key(pattern)) {
+            self.issue(None, Problem::ImpossiblePattern { pattern: *pattern });
+        }
+
+        for (pattern, pattern_answers) in groups {
+            if Checker::is_success_pattern(pattern, word_len) {
+                self.report.solved += 1;
+                let turns = self.path.len() as u32 + 1;
+                if turns > self.turn_cap {
+                    self.issue(Some((guess, pattern)), Problem::TooDeep { answer: guess, turns });
+                }
+                continue;
+            }
+
+            let Some(child) = node.branch.get(&pattern) else {
+                self.issue(Some((guess, pattern)), Problem::Unsolved { answers: pattern_answers.iter().collect() });
+                continue;
+            };
+
+            let next_allowed = if self.hard_mode {
+                filter_available_guesses(dictionary, &Restriction::from(dictionary.word(guess), pattern), allowed)
+            } else {
+                allowed.clone()
+            };
+
+            self.path.push((guess, pattern));
+            self.visit(child, &pattern_answers, &next_allowed);
+            self.path.pop();
+        }
+    }
+}
+
+// Plays `tree` for every answer, guessing from `availables` under the hard or
+// normal mode rules, and collects every problem instead of stopping at the
+// first. Answers need at most `turn_cap` guesses.
+pub fn validate(tree: &DecisionTree, answers: &AnswerSet, availables: &BTreeSet<WordId>, table: &PatternTable, hard_mode: bool, turn_cap: u32) -> ValidationReport {
+    let mut walk = Walk {
+        table,
+        hard_mode,
+        turn_cap,
+        path: Vec::new(),
+        report: ValidationReport {
+            answer_count: answers.len(),
+            solved: 0,
+            issues: Vec::new()
+        }
+    };
+
+    walk.visit(tree, answers, availables);
+    walk.report
+}
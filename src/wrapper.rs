@@ -1,273 +1,309 @@
-use std::sync::{Arc, Mutex};
 use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::time::{Duration, Instant};
 
 use rayon::prelude::*;
 
 use crate::game::{Checker};
-use crate::common::{Best, DecisionTree, Restriction, Task, Cache, Counter};
+use crate::common::{Best, Task, Pattern};
+use crate::config::{SearchConfig, Beam};
 use crate::utils::*;
-use crate::dfs::{dfs, dfs_with_cache};    
-
-pub fn parallel_wrapper<'a>(start_word: &'a str, answers: &BTreeSet<&'a str>, availables: &BTreeSet<&'a str>) -> Best<'a> {
-    let mut tasks :BTreeSet<Task<'a>> = BTreeSet::new();
-
-    let groups = group_by_pattern(start_word, answers);
-    let mut sorted_groups: Vec<_> = groups.into_iter().collect();
-    sorted_groups.sort_unstable_by_key(|(_, g)| g.len()); 
+use crate::dfs::Searcher;
+use crate::table::PatternTable;
+use crate::dictionary::WordId;
+use crate::answer_set::AnswerSet;
+use crate::checkpoint::Checkpoint;
+use crate::observer::{Event, SearchStats};
+
+// Subtree below the start word for one of its patterns.
+fn search_after_start(searcher: &Searcher, start_word: WordId, pattern: Pattern, pattern_answers: &AnswerSet, availables: &BTreeSet<WordId>) -> Best {
+    let word_len = searcher.table.word_len();
+
+    if Checker::is_success_pattern(pattern, word_len) {
+        Best::success()
+    } else if pattern_answers.len() == 1 {
+        searcher.single(pattern_answers)
+    } else {
+        let new_availables = searcher.next_availables(start_word, pattern, pattern_answers, availables);
+        searcher.search(1, pattern_answers, &new_availables)
+    }
+}
 
-    let mut start_best = Best::init(start_word, answers.len() as u32);
+// What parallel_wrapper settles before searching: the start word's trivial
+// branches and one task per (pattern, second guess, second pattern) left.
+pub struct TaskPlan {
+    pub start_word: WordId,
+    pub start_best: Best,
+    pub pattern_groups: BTreeMap<Pattern, AnswerSet>,
+    // Best ranked second guess per pattern, the only one still searched once the budget runs out.
+    pub first_guesses: BTreeMap<Pattern, WordId>,
+    pub tasks: BTreeSet<Task>
+}
 
-    let mut answers_count: BTreeMap<u8, usize> = BTreeMap::new();
+impl TaskPlan {
+    pub fn new(searcher: &Searcher, start_word: WordId, answers: &AnswerSet, availables: &BTreeSet<WordId>) -> Self {
+        let word_len = searcher.table.word_len();
 
-    for (pattern, pattern_answers) in sorted_groups {
+        let groups = group_by_pattern(searcher.table, start_word, answers);
+        let mut sorted_groups: Vec<_> = groups.into_iter().collect();
+        sorted_groups.sort_unstable_by_key(|(_, g)| g.len());
 
-        if Checker::is_success_pattern(pattern) {
-            start_best.update(pattern, Best{
-                has_result: true,
-                max_level: 0,
-                total_count: 0,
-                decision_tree: DecisionTree::new()
-            });
-            continue;
-        }
+        let mut plan = TaskPlan {
+            start_word,
+            start_best: searcher.init(start_word, answers),
+            pattern_groups: BTreeMap::new(),
+            first_guesses: BTreeMap::new(),
+            tasks: BTreeSet::new()
+        };
 
-        if pattern_answers.len() == 1{
-            start_best.update(pattern, Best {
-                has_result: true,
-                max_level: 1,
-                total_count: 1,
-                decision_tree: DecisionTree::from(pattern_answers.iter().nth(0).unwrap(), BTreeMap::from([(242, DecisionTree::new())]))
-            });
-            continue;
-        };   
+        for (pattern, pattern_answers) in sorted_groups {
 
-        answers_count.insert(pattern, pattern_answers.len());
+            if Checker::is_success_pattern(pattern, word_len) {
+                plan.start_best.update(pattern, Best::success());
+                continue;
+            }
 
-        let restriction = Restriction::from(start_word, pattern);
-        let available_guesses = filter_available_guesses(&restriction, &availables);
+            if pattern_answers.len() == 1{
+                plan.start_best.update(pattern, searcher.single(&pattern_answers));
+                continue;
+            };
 
-        let mut group_patterns = BTreeSet::<BTreeMap<u8, BTreeSet<&str>>>::new();
-        for second_guess in available_guesses.iter() {
-            let second_groups = group_by_pattern(second_guess, &pattern_answers);
+            let available_guesses = searcher.narrow(start_word, pattern, availables);
 
-            if group_patterns.contains(&second_groups) {
-                continue
+            for (second_guess, _, second_groups) in searcher.rank(&pattern_answers, &available_guesses, usize::MAX) {
+                plan.first_guesses.entry(pattern).or_insert(second_guess);
+                for (second_pattern, _) in second_groups {
+                    plan.tasks.insert((pattern, second_guess, second_pattern));
+                }
             }
-            group_patterns.insert(second_groups.clone());
 
-            for (second_pattern, _) in second_groups {
-                tasks.insert((pattern, second_guess, second_pattern));
-            }
+            plan.pattern_groups.insert(pattern, pattern_answers);
+
         }
 
+        plan
     }
 
-    println!("Prepared Tasks.");
+    // The subtree after the start word and second guess showed the task's patterns.
+    pub fn solve(&self, searcher: &Searcher, task: &Task, answers: &AnswerSet, availables: &BTreeSet<WordId>) -> Best {
+        let (pattern, second_guess, second_pattern) = task;
+        let table = searcher.table;
 
-    let bests: Vec<_> = tasks.par_iter().map(|(pattern, second_guess, second_pattern)|{
-        if Checker::is_success_pattern(*second_pattern) {
-            return (pattern, second_guess, second_pattern, Best{
-                has_result: true,
-                max_level: 0,
-                total_count: 0,
-                decision_tree: DecisionTree::new()
-            });
+        if Checker::is_success_pattern(*second_pattern, table.word_len()) {
+            return Best::success();
         }
 
-        let first_restriction = Restriction::from(start_word, *pattern);
-        let second_restriction = Restriction::from(second_guess, *second_pattern);
-        
-        let available_guesses = filter_available_guesses(&first_restriction, availables);
-        let available_guesses = filter_available_guesses(&second_restriction, &available_guesses);
-
-        if available_guesses.len() == 1 {
-            return (pattern, second_guess, second_pattern, Best{
-                has_result: true,
-                max_level: 1,
-                total_count: 1,
-                decision_tree: DecisionTree::from(available_guesses.iter().nth(0).unwrap(), BTreeMap::from([(242, DecisionTree::new())]))
-            })
+        let available_answers = filter_available_answers(table, self.start_word, *pattern, answers);
+        let available_answers = filter_available_answers(table, *second_guess, *second_pattern, &available_answers);
+
+        if available_answers.len() == 1 {
+            return searcher.single(&available_answers)
         }
 
-        let available_answers = filter_available_answers(start_word, *pattern, answers);
-        let available_answers = filter_available_answers(second_guess, *second_pattern, &available_answers);
+        if searcher.clock.exhausted() && self.first_guesses[pattern] != *second_guess {
+            return Best::new()
+        }
 
-        (pattern, second_guess, second_pattern, dfs(2, &available_answers, &available_guesses))
-    }).collect();
+        let available_guesses = searcher.narrow(self.start_word, *pattern, availables);
+        let available_guesses = searcher.narrow(*second_guess, *second_pattern, &available_guesses);
 
-    let mut results: BTreeMap<u8, BTreeMap<&str, BTreeMap<u8, Best>>> = BTreeMap::new();
+        searcher.search(2, &available_answers, &available_guesses)
+    }
 
-    println!("Finished Tasks.");
+    // Builds the start word's tree from the results of every task, in whatever order they come.
+    pub fn reduce<I: IntoIterator<Item = (Task, Best)>>(self, searcher: &Searcher, results: I, availables: &BTreeSet<WordId>) -> Best {
+        let mut by_pattern: BTreeMap<Pattern, BTreeMap<WordId, BTreeMap<Pattern, Best>>> = BTreeMap::new();
+
+        for ((pattern, second_guess, second_pattern), best) in results {
+            by_pattern
+                .entry(pattern)
+                .or_default()
+                .entry(second_guess)
+                .or_default()
+                .insert(second_pattern, best);
+        }
 
-    for (pattern, second_guess, second_pattern, best) in bests {
-        results
-            .entry(*pattern)
-            .or_insert_with(BTreeMap::new)
-            .entry(second_guess)
-            .or_insert_with(BTreeMap::new)
-            .insert(*second_pattern, best);
-    }
+        let mut start_best = self.start_best;
 
-    for (pattern, pattern_results) in results {
-        let mut best_of_all_guess = Best::new();
+        for (pattern, pattern_answers) in self.pattern_groups.iter() {
+            let mut best_of_all_guess = Best::new();
 
-        for (second_guess, second_guess_result) in pattern_results {
-            let answer_count = answers_count.get(&pattern).unwrap();
+            for (second_guess, second_guess_result) in by_pattern.remove(pattern).unwrap_or_default() {
+                let mut current_guess = searcher.init(second_guess, pattern_answers);
 
-            let mut current_guess = Best::init(second_guess, *answer_count as u32);
+                for (second_pattern, best) in second_guess_result {
+                    if best.has_result {
+                        current_guess.update(second_pattern, best);
+                    } else {
+                        current_guess.has_result = false;
+                        break
+                    }
+                }
 
-            for (second_pattern, best) in second_guess_result {
-                if best.has_result {
-                    current_guess.update(second_pattern, best);
-                } else {
-                    current_guess.has_result = false;
-                    break
+                if current_guess.has_result {
+                    best_of_all_guess.better(current_guess, &*searcher.config.objective);
                 }
             }
 
-            if current_guess.has_result {
-                best_of_all_guess.better(current_guess);
+            // Every second guess ran out of budget or turns, finish the pattern on its own.
+            if !best_of_all_guess.has_result {
+                best_of_all_guess = search_after_start(searcher, self.start_word, *pattern, pattern_answers, availables);
+            }
+
+            if !best_of_all_guess.has_result {
+                return Best::new();
             }
+            start_best.update(*pattern, best_of_all_guess);
         }
 
-        assert_eq!(best_of_all_guess.has_result, true);
-        start_best.update(pattern, best_of_all_guess);
+        start_best.finish();
+        start_best
     }
+}
 
-    println!("Found Best.");
+// Times the stages of a wrapper and tells the observer as each one ends.
+struct Stages {
+    started: Instant,
+    stages: Vec<(&'static str, Duration)>
+}
+
+impl Stages {
+    fn start() -> Self {
+        Stages { started: Instant::now(), stages: Vec::new() }
+    }
+
+    fn stats(&self, searcher: &Searcher) -> SearchStats {
+        SearchStats { stages: self.stages.clone(), ..searcher.stats() }
+    }
 
-    start_best.max_level += 1;
-    return start_best
+    fn finish(&mut self, name: &'static str, searcher: &Searcher) {
+        self.stages.push((name, self.started.elapsed()));
+        self.started = Instant::now();
+        searcher.config.observer.notify(&Event::StageDone { name, stats: &self.stats(searcher) });
+    }
 }
 
-pub fn start_word_wrapper<'a>(start_word: &'a str, answers: &BTreeSet<&'a str>, availables: &BTreeSet<&'a str>) -> Best<'a> {
-    let groups = group_by_pattern(start_word, answers);
-    let mut current_guess = Best::init(start_word, answers.len() as u32);
+// Fails when the checkpoint file cannot be used: written for another run, not
+// readable or not writable. Starting over would lose the tasks it holds.
+pub fn parallel_wrapper(start_word: WordId, answers: &AnswerSet, availables: &BTreeSet<WordId>, table: &PatternTable, config: &SearchConfig) -> io::Result<(Best, SearchStats)> {
+    let searcher = Searcher::new(table, config.clone());
+    let mut stages = Stages::start();
+    let plan = TaskPlan::new(&searcher, start_word, answers, availables);
+
+    let (checkpoint, done) = match &config.checkpoint_path {
+        Some(path) => {
+            let (checkpoint, done) = Checkpoint::<Task>::open(path, Checkpoint::fingerprint(table.dictionary(), config, start_word, answers, availables))?;
+            (Some(checkpoint), done)
+        },
+        None => (None, BTreeMap::new())
+    };
 
-    let mut sorted_groups: Vec<_> = groups.into_iter().collect();
-    sorted_groups.sort_unstable_by_key(|(_, g)| g.len()); 
+    if !done.is_empty() {
+        config.observer.notify(&Event::Resumed { tasks: done.len() });
+    }
+    stages.finish("plan", &searcher);
 
-    let bests: Vec<_> = sorted_groups.iter().map(|(pattern, pattern_answers)| {
-        let best = if Checker::is_success_pattern(*pattern) {
-            Best {
-                has_result: true,
-                max_level: 0,
-                total_count: 0,
-                decision_tree: DecisionTree::new()
-            }
-        } else if pattern_answers.len() == 1{
-            Best {
-                has_result: true,
-                max_level: 1,
-                total_count: 1,
-                decision_tree: DecisionTree::from(pattern_answers.iter().nth(0).unwrap(), BTreeMap::from([(242, DecisionTree::new())]))
-            }
-        } else if pattern_answers.len() <= 3 {
-            dfs(1, &pattern_answers, &pattern_answers)
-        } else {
-            let new_restrictions = Restriction::from(start_word, *pattern);
-            dfs(1, &pattern_answers, &filter_available_guesses(&new_restrictions, &availables))
-        };
-        (pattern, best)
-    }).collect();
+    let total = plan.tasks.len() - done.len();
+    let finished = AtomicUsize::new(0);
 
-    for (pattern, best) in bests {
+    // Every task searches with the same searcher, so they share its cache and budget.
+    let bests = plan.tasks.par_iter().filter(|task| !done.contains_key(task)).map(|task| {
+        let best = plan.solve(&searcher, task, answers, availables);
+
+        // A result cut short by the budget is not what a resumed run would find.
+        if let Some(checkpoint) = checkpoint.as_ref().filter(|_| !searcher.clock.exhausted()) {
+            checkpoint.record(task, &best)?;
+        }
+
+        let done = finished.fetch_add(1, AtomicOrdering::Relaxed) + 1;
+        config.observer.notify(&Event::TaskDone { done, total });
+        Ok((*task, best))
+    }).collect::<io::Result<Vec<_>>>()?;
+
+    stages.finish("tasks", &searcher);
+
+    let best = plan.reduce(&searcher, done.into_iter().chain(bests), availables);
+
+    stages.finish("reduce", &searcher);
+
+    Ok((best, stages.stats(&searcher)))
+}
+
+// The tree for `start_word` from a searcher the caller owns, so several openers can share its cache.
+pub fn start_word_search(searcher: &Searcher, start_word: WordId, answers: &AnswerSet, availables: &BTreeSet<WordId>) -> Best {
+    let groups = group_by_pattern(searcher.table, start_word, answers);
+    let mut current_guess = searcher.init(start_word, answers);
+
+    let mut sorted_groups: Vec<_> = groups.into_iter().collect();
+    sorted_groups.sort_unstable_by_key(|(_, g)| g.len());
+
+    for (pattern, pattern_answers) in sorted_groups.iter() {
+        let best = search_after_start(searcher, start_word, *pattern, pattern_answers, availables);
+        if !best.has_result {
+            return Best::new();
+        }
         current_guess.update(*pattern, best);
     }
 
-    current_guess.max_level += 1;
+    current_guess.finish();
     current_guess
 }
 
-pub fn baseline_wrapper<'a>(start_word: &'a str, answers: &BTreeSet<&'a str>, availables: &BTreeSet<&'a str>) -> Best<'a> {
-    let groups = group_by_pattern(start_word, answers);
-    let mut current_guess = Best::init(start_word, answers.len() as u32);
+// Fails when the cache file from `config` cannot be written.
+pub fn start_word_wrapper(start_word: WordId, answers: &AnswerSet, availables: &BTreeSet<WordId>, table: &PatternTable, config: &SearchConfig) -> io::Result<(Best, SearchStats)> {
+    let mut searcher = Searcher::new(table, config.clone());
+    searcher.load_cache();
+    let mut stages = Stages::start();
 
-    let mut sorted_groups: Vec<_> = groups.into_iter().collect();
-    sorted_groups.sort_unstable_by_key(|(_, g)| g.len()); 
+    let best = start_word_search(&searcher, start_word, answers, availables);
+    stages.finish("search", &searcher);
+
+    searcher.save_cache()?;
+    Ok((best, stages.stats(&searcher)))
+}
 
-    let cache = Arc::new(Mutex::new(Cache::new()));
+// Runs the beam from `config` to fill the cache, then an unlimited pass. What
+// the beam cut guesses from only seeds the second pass as a baseline to beat,
+// so it searches those states again with every guess. Fails like start_word_wrapper.
+pub fn baseline_wrapper(start_word: WordId, answers: &AnswerSet, availables: &BTreeSet<WordId>, table: &PatternTable, config: &SearchConfig) -> io::Result<(Best, SearchStats)> {
+    let groups = group_by_pattern(table, start_word, answers);
 
-    let mut counter = Counter {
-        result_counter: 0,
-        no_result_counter: 0,
-        baseline_counter: 0
-    };
+    let mut sorted_groups: Vec<_> = groups.into_iter().collect();
+    sorted_groups.sort_unstable_by_key(|(_, g)| g.len());
+
+    let mut searcher = Searcher::new(table, SearchConfig {
+        use_cache: true,
+        ..config.clone()
+    });
+    searcher.load_cache();
+    let mut stages = Stages::start();
+    let mut current_guess = searcher.init(start_word, answers);
 
     sorted_groups.iter().for_each(|(pattern, pattern_answers)| {
-        if Checker::is_success_pattern(*pattern) {
-            Best {
-                has_result: true,
-                max_level: 0,
-                total_count: 0,
-                decision_tree: DecisionTree::new()
-            }
-        } else if pattern_answers.len() == 1{
-            Best {
-                has_result: true,
-                max_level: 1,
-                total_count: 1,
-                decision_tree: DecisionTree::from(pattern_answers.iter().nth(0).unwrap(), BTreeMap::from([(242, DecisionTree::new())]))
-            }
-        } else if pattern_answers.len() <= 3 {
-            let new_restrictions = Restriction::from(start_word, *pattern);
-            dfs_with_cache(1, &pattern_answers, &pattern_answers, new_restrictions, &cache, true, &mut counter)
-        } else {
-            let new_restrictions = Restriction::from(start_word, *pattern);
-            dfs_with_cache(1, &pattern_answers, &filter_available_guesses(&new_restrictions, &availables), new_restrictions, &cache, true, &mut counter)
-        };
+        search_after_start(&searcher, start_word, *pattern, pattern_answers, availables);
     });
 
-    /*
-        First Stage Finished.
-            Counter: Counter { result_counter: 432, no_result_counter: 0, baseline_counter: 33 }
-        Second Stage Finished.
-            Counter: Counter { result_counter: 18247, no_result_counter: 0, baseline_counter: 130 }
-
-        Second Stage Finished.
-            Counter: Counter { result_counter: 17627, no_result_counter: 0, baseline_counter: 170 }
-    */
-    println!("First Stage Finished.");
-    println!("Counter: {:?}", counter);
-
-    let mut counter = Counter {
-        result_counter: 0,
-        no_result_counter: 0,
-        baseline_counter: 0
-    };
+    stages.finish("beam", &searcher);
+
+    searcher.config.beam = Beam::Unlimited;
 
     let bests: Vec<_> = sorted_groups.iter().map(|(pattern, pattern_answers)| {
-        let best = if Checker::is_success_pattern(*pattern) {
-            Best {
-                has_result: true,
-                max_level: 0,
-                total_count: 0,
-                decision_tree: DecisionTree::new()
-            }
-        } else if pattern_answers.len() == 1{
-            Best {
-                has_result: true,
-                max_level: 1,
-                total_count: 1,
-                decision_tree: DecisionTree::from(pattern_answers.iter().nth(0).unwrap(), BTreeMap::from([(242, DecisionTree::new())]))
-            }
-        } else if pattern_answers.len() <= 3 {
-            let new_restrictions = Restriction::from(start_word, *pattern);
-            dfs_with_cache(1, &pattern_answers, &pattern_answers, new_restrictions, &cache, false, &mut counter)
-        } else {
-            let new_restrictions = Restriction::from(start_word, *pattern);
-            dfs_with_cache(1, &pattern_answers, &filter_available_guesses(&new_restrictions, &availables), new_restrictions, &cache, false, &mut counter)
-        };
-        (pattern, best)
+        (pattern, search_after_start(&searcher, start_word, *pattern, pattern_answers, availables))
     }).collect();
 
-    println!("Second Stage Finished.");
-    println!("Counter: {:?}", counter);
+    stages.finish("unlimited", &searcher);
+    let stats = stages.stats(&searcher);
+
+    searcher.save_cache()?;
 
     for (pattern, best) in bests {
+        if !best.has_result {
+            return Ok((Best::new(), stats));
+        }
         current_guess.update(*pattern, best);
     }
 
-    current_guess.max_level += 1;
-    current_guess
-}
\ No newline at end of file
+    current_guess.finish();
+    Ok((current_guess, stats))
+}